@@ -0,0 +1,61 @@
+//! Pinnacle Open API definition don't differentiate non optional response fields.
+//! This check loops through sports reporting every response field which doesn't match our
+//! structs: null or missing non optional fields, unknown enum variants and unmodeled fields.
+//! Unsuccessful responses, e.g. on wrong credentials or server errors, fail the check too.
+//!
+//! Runs against the live API, or against recorded cassettes with `--cassettes`.
+use clap::Parser;
+use pinnacle::prelude::*;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[arg(env, long, required_unless_present = "cassettes")]
+    pinnacle_username: Option<String>,
+
+    #[arg(env, long, required_unless_present = "cassettes")]
+    pinnacle_password: Option<String>,
+
+    /// Check the recorded cassettes instead of the live API
+    #[arg(long)]
+    cassettes: Option<PathBuf>,
+}
+
+fn progress(i: usize, n: usize, sport: &Sport) {
+    eprintln!("{i} of {n}: {}", sport.name);
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    dotenvy::dotenv().ok();
+
+    let cli = Cli::parse();
+    let issues = match cli.cassettes {
+        Some(dir) => {
+            let client = PinnacleReplayClient::replay(dir);
+            let checker = SchemaChecker::new(&client).with_progress(progress);
+            checker.check_all().await?
+        }
+        None => {
+            let client = PinnacleClient::new(
+                cli.pinnacle_username.unwrap_or_default(),
+                cli.pinnacle_password.unwrap_or_default(),
+            );
+            let checker = SchemaChecker::new(&client).with_progress(progress);
+            checker.check_all().await?
+        }
+    };
+
+    let summary = summarize(&issues);
+    for issue in summary.iter() {
+        println!("{issue}\n");
+    }
+    println!("{} issues in {} fields", issues.len(), summary.len());
+    Ok(if issues.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
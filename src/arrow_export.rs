@@ -0,0 +1,350 @@
+//! Columnar export of odds, fixtures and settled fixtures into Arrow [`RecordBatch`]es and
+//! Parquet files, e.g. for research in Polars.
+//!
+//! Odds have a row per price, fixtures a row per event and settled fixtures a row per settled
+//! period. Times are UTC millisecond timestamps, limits and other optional fields are nullable.
+//! [`ParquetExporter`] writes the batches into a hive-style `{kind}/sport_id={id}/date={date}`
+//! folder structure, where `date` is the UTC date of the export. Each batch goes into a new
+//! file, so concurrent exporters never overwrite each other. Enums are stored with their serde
+//! names, the same as in the CSV and NDJSON exports.
+//!
+//! Requires the `arrow` feature.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = PinnacleClient::new("pinnacle_user", "pinnacle_password");
+//! let odds = client
+//!     .get(&GetStraightOdds {
+//!         sport_id: 29,
+//!         ..Default::default()
+//!     })
+//!     .await?;
+//! let path = ParquetExporter::new("research").write_odds(&odds)?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::responses::{FixturesResponse, OddsResponse, SettledFixturesResponse};
+use crate::util::parse_starts;
+use arrow::array::{
+    ArrayRef, Float64Array, Int32Array, Int64Array, RecordBatch, StringArray,
+    TimestampMillisecondArray,
+};
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::error::ArrowError;
+use chrono::{DateTime, NaiveDate, Utc};
+use displaydoc::Display;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use thiserror::Error;
+use uuid::Uuid;
+
+/// Writes batches as Parquet files partitioned by sport and date
+#[derive(Debug, Clone)]
+pub struct ParquetExporter {
+    dir: PathBuf,
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum ArrowExportError {
+    /// arrow
+    Arrow(#[from] ArrowError),
+    /// parquet
+    Parquet(#[from] ParquetError),
+    /// io
+    Io(#[from] io::Error),
+}
+
+impl ParquetExporter {
+    /// Creates an exporter writing into the `dir` folder
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Writes the odds, returns the path of the file
+    pub fn write_odds(&self, odds: &OddsResponse) -> Result<PathBuf, ArrowExportError> {
+        let batch = odds_batch(odds)?;
+        self.write("odds", odds.sport_id, Utc::now().date_naive(), &batch)
+    }
+
+    /// Writes the fixtures, returns the path of the file
+    pub fn write_fixtures(&self, fixtures: &FixturesResponse) -> Result<PathBuf, ArrowExportError> {
+        let batch = fixtures_batch(fixtures)?;
+        self.write(
+            "fixtures",
+            fixtures.sport_id,
+            Utc::now().date_naive(),
+            &batch,
+        )
+    }
+
+    /// Writes the settled fixtures, returns the path of the file
+    pub fn write_settled(
+        &self,
+        settled: &SettledFixturesResponse,
+    ) -> Result<PathBuf, ArrowExportError> {
+        let batch = settled_batch(settled)?;
+        self.write("settled", settled.sport_id, Utc::now().date_naive(), &batch)
+    }
+
+    /// Writes a batch into the `{kind}/sport_id={sport_id}/date={date}` partition
+    pub fn write(
+        &self,
+        kind: &str,
+        sport_id: i32,
+        date: NaiveDate,
+        batch: &RecordBatch,
+    ) -> Result<PathBuf, ArrowExportError> {
+        let dir = self
+            .dir
+            .join(kind)
+            .join(format!("sport_id={sport_id}"))
+            .join(format!("date={date}"));
+        fs::create_dir_all(&dir)?;
+        let name = format!(
+            "part-{}-{}.parquet",
+            Utc::now().timestamp_millis(),
+            Uuid::new_v4()
+        );
+        let path = dir.join(name);
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(batch)?;
+        writer.close()?;
+        Ok(path)
+    }
+}
+
+/// Odds as a row per price
+pub fn odds_batch(odds: &OddsResponse) -> Result<RecordBatch, ArrowError> {
+    let mut rows = Vec::new();
+    for league in odds.leagues.iter() {
+        for event in league.events.iter() {
+            for period in event.periods.iter() {
+                for price in period.prices() {
+                    rows.push((league.id, event.id, period, price));
+                }
+            }
+        }
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("sport_id", DataType::Int32, false),
+        Field::new("league_id", DataType::Int32, false),
+        Field::new("event_id", DataType::Int64, false),
+        Field::new("period_number", DataType::Int32, false),
+        Field::new("line_id", DataType::Int64, false),
+        Field::new("cutoff", timestamp(), false),
+        Field::new("status", DataType::Int32, false),
+        Field::new("market_type", DataType::Utf8, false),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("line", DataType::Float64, true),
+        Field::new("price", DataType::Float64, false),
+        Field::new("alt_line_id", DataType::Int64, true),
+        Field::new("max", DataType::Float64, true),
+        Field::new("updated_at", timestamp(), true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![odds.sport_id; rows.len()])),
+        Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.0))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.1))),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.2.number),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|r| r.2.line_id),
+        )),
+        timestamps(rows.iter().map(|r| Some(r.2.cutoff))),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.2.status),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| serde_name(&r.3.market_type)),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| serde_name(&r.3.side)),
+        )),
+        Arc::new(Float64Array::from_iter(rows.iter().map(|r| r.3.line))),
+        Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|r| r.3.price),
+        )),
+        Arc::new(Int64Array::from_iter(rows.iter().map(|r| r.3.alt_line_id))),
+        Arc::new(Float64Array::from_iter(rows.iter().map(|r| r.3.max))),
+        timestamps(rows.iter().map(|r| r.3.updated_at)),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Fixtures as a row per event
+pub fn fixtures_batch(fixtures: &FixturesResponse) -> Result<RecordBatch, ArrowError> {
+    let rows: Vec<_> = fixtures
+        .league
+        .iter()
+        .flat_map(|league| league.events.iter().map(move |f| (league, f)))
+        .collect();
+
+    let schema = Schema::new(vec![
+        Field::new("sport_id", DataType::Int32, false),
+        Field::new("league_id", DataType::Int32, false),
+        Field::new("league", DataType::Utf8, false),
+        Field::new("event_id", DataType::Int64, false),
+        Field::new("parent_id", DataType::Int64, true),
+        Field::new("starts", timestamp(), true),
+        Field::new("home", DataType::Utf8, false),
+        Field::new("away", DataType::Utf8, false),
+        Field::new("live_status", DataType::Int32, false),
+        Field::new("parlay_restriction", DataType::Int32, false),
+        Field::new("resulting_unit", DataType::Utf8, true),
+        Field::new("version", DataType::Int64, false),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![fixtures.sport_id; rows.len()])),
+        Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.0.id))),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.0.name),
+        )),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.1.id))),
+        Arc::new(Int64Array::from_iter(rows.iter().map(|r| r.1.parent_id))),
+        timestamps(rows.iter().map(|r| parse_starts(&r.1.starts))),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.1.home),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            rows.iter().map(|r| &r.1.away),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.1.live_status),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.1.parlay_restriction),
+        )),
+        Arc::new(StringArray::from_iter(
+            rows.iter().map(|r| r.1.resulting_unit.as_deref()),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|r| r.1.version),
+        )),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Settled fixtures as a row per settled period
+pub fn settled_batch(settled: &SettledFixturesResponse) -> Result<RecordBatch, ArrowError> {
+    let mut rows = Vec::new();
+    for league in settled.leagues.iter() {
+        for event in league.events.iter() {
+            for period in event.periods.iter() {
+                rows.push((league.id, event.id, period));
+            }
+        }
+    }
+
+    let schema = Schema::new(vec![
+        Field::new("sport_id", DataType::Int32, false),
+        Field::new("league_id", DataType::Int32, false),
+        Field::new("event_id", DataType::Int64, false),
+        Field::new("period_number", DataType::Int32, false),
+        Field::new("status", DataType::Int32, false),
+        Field::new("settlement_id", DataType::Int64, false),
+        Field::new("settled_at", timestamp(), false),
+        Field::new("team1_score", DataType::Float64, true),
+        Field::new("team2_score", DataType::Float64, true),
+    ]);
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int32Array::from(vec![settled.sport_id; rows.len()])),
+        Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.0))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|r| r.1))),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.2.number),
+        )),
+        Arc::new(Int32Array::from_iter_values(
+            rows.iter().map(|r| r.2.status),
+        )),
+        Arc::new(Int64Array::from_iter_values(
+            rows.iter().map(|r| r.2.settlement_id),
+        )),
+        timestamps(rows.iter().map(|r| Some(r.2.settled_at))),
+        Arc::new(Float64Array::from_iter(
+            rows.iter().map(|r| r.2.team1_score),
+        )),
+        Arc::new(Float64Array::from_iter(
+            rows.iter().map(|r| r.2.team2_score),
+        )),
+    ];
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Name of a unit enum variant as serialized by serde
+fn serde_name<T: Serialize>(value: &T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+fn timestamp() -> DataType {
+    DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into()))
+}
+
+fn timestamps(values: impl Iterator<Item = Option<DateTime<Utc>>>) -> ArrayRef {
+    let array =
+        TimestampMillisecondArray::from_iter(values.map(|t| t.map(|t| t.timestamp_millis())));
+    Arc::new(array.with_timezone("UTC"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_json;
+    use arrow::array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    #[test]
+    fn test_odds_parquet() {
+        let odds: OddsResponse = parse_json(
+            r#"{"sportId": 29, "last": 2, "leagues": [{"id": 1, "events": [{"id": 10,
+                "periods": [{"lineId": 100, "number": 0, "cutoff": "2023-04-16T18:00:00Z",
+                "status": 1, "maxMoneyline": 300, "moneyline": {"home": 2.1, "away": 3.5},
+                "totals": [{"points": 2.5, "over": 1.9, "under": 1.95}]}]}]}]}"#,
+        )
+        .unwrap();
+        let batch = odds_batch(&odds).unwrap();
+        assert_eq!(batch.num_rows(), 4);
+        assert_eq!(batch.column_by_name("max").unwrap().null_count(), 2);
+        assert_eq!(batch.column_by_name("line").unwrap().null_count(), 2);
+        let market_types = batch.column_by_name("market_type").unwrap();
+        let market_types = market_types.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!(
+            market_types.value(0),
+            serde_json::to_value(crate::market_book::MarketType::Moneyline).unwrap()
+        );
+
+        let dir = std::env::temp_dir().join(format!("pinnacle-parquet-{}", std::process::id()));
+        let path = ParquetExporter::new(&dir).write_odds(&odds).unwrap();
+        assert!(path.starts_with(dir.join("odds").join("sport_id=29")));
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(batches, vec![batch]);
+
+        // Batches written at once go into separate files
+        let batch = odds_batch(&odds).unwrap();
+        let exporter = ParquetExporter::new(&dir);
+        let date = Utc::now().date_naive();
+        let a = exporter.write("odds", 29, date, &batch).unwrap();
+        let b = exporter.write("odds", 29, date, &batch).unwrap();
+        assert_ne!(a, b);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,525 @@
+//! Safety guardrails for bet placement, protecting against bugs in betting strategies.
+//!
+//! [`BetGuard`] wraps a client and checks every bet against the configured limits before it's
+//! placed. Limits apply to the amount at risk, which for `WIN` stakes is found from the price.
+//! Exposure is the sum of the amounts at risk of the bets placed through the guard. A bet is
+//! counted before it's sent, corrected to the risk Pinnacle reports once placed, and uncounted
+//! only when it definitely wasn't placed, so bets with unknown outcome, e.g. timed out ones,
+//! still count. Dry runs are counted as if the bets were placed, so a strategy hits the
+//! limits the same way in the dry-run mode.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let guard = BetGuard::new(
+//!     PinnacleClient::new("pinnacle_user", "pinnacle_password"),
+//!     BetGuardConfig {
+//!         dry_run: true,
+//!         max_stake: Some(100.0),
+//!         max_daily_exposure: Some(1000.0),
+//!         ..Default::default()
+//!     },
+//! );
+//! let kill_switch = guard.kill_switch();
+//! # let bet = PlaceStraightBet {
+//! #     odds_format: OddsFormat::Decimal,
+//! #     unique_request_id: String::new(),
+//! #     accept_better_line: true,
+//! #     stake: 10.0,
+//! #     win_risk_stake: WinRiskStake::Risk,
+//! #     line_id: 1,
+//! #     alt_line_id: None,
+//! #     fill_type: FillType::Normal,
+//! #     sport_id: 29,
+//! #     event_id: 1,
+//! #     period_number: 0,
+//! #     bet_type: BetType::Moneyline,
+//! #     team: Some(Team::Team1),
+//! #     side: None,
+//! # };
+//! let (league_id, price) = (1980, 1.95);
+//! match guard.place(&bet, league_id, price).await {
+//!     Ok(outcome) => println!("{outcome:?}"),
+//!     Err(BetGuardError::Rejected(rule)) => eprintln!("rejected: {rule}"),
+//!     Err(e) => kill_switch.engage(),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::client::PinnacleClientError;
+use crate::requests::{FillType, PlaceStraightBet, WinRiskStake};
+use crate::responses::{PlaceBetResponse, PlaceBetStatus};
+use crate::traits::{PinnacleApiClient, PinnacleApiPostRequest};
+use crate::util::is_definitive_failure;
+use chrono::{NaiveDate, Utc};
+use displaydoc::Display;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use thiserror::Error;
+
+/// Limits of a [`BetGuard`], `None` means no limit
+#[derive(Debug, Clone, Default)]
+pub struct BetGuardConfig {
+    /// Log and simulate bets instead of placing them, their exposure is still counted
+    pub dry_run: bool,
+    /// Maximum amount at risk of a single bet
+    pub max_stake: Option<f64>,
+    /// Maximum total amount at risk on a single event
+    pub max_event_exposure: Option<f64>,
+    /// Maximum total amount at risk on a single league
+    pub max_league_exposure: Option<f64>,
+    /// Maximum total amount at risk per UTC day
+    pub max_daily_exposure: Option<f64>,
+    /// Allow `FILLMAXLIMIT` bets. Pinnacle ignores their stake, so until they're placed the
+    /// limits can only count them by the stake.
+    pub allow_fill_max_limit: bool,
+}
+
+/// Places bets, checking them against the limits
+#[derive(Debug)]
+pub struct BetGuard<C> {
+    client: C,
+    config: BetGuardConfig,
+    exposure: Mutex<Exposure>,
+    kill_switch: KillSwitch,
+}
+
+/// Stops all the bets of a guard while engaged, can be cloned and engaged from anywhere
+#[derive(Debug, Clone, Default)]
+pub struct KillSwitch(Arc<AtomicBool>);
+
+/// Result of a bet allowed by the guard
+#[derive(Debug, Clone)]
+pub enum BetOutcome {
+    /// The bet is sent to Pinnacle
+    Placed(Box<PlaceBetResponse>),
+    /// The bet is only logged because of the dry-run mode
+    DryRun,
+}
+
+/// A rule of the guard rejecting a bet
+#[derive(Debug, Display, Error, Clone, PartialEq)]
+pub enum GuardRejected {
+    /// kill switch is engaged
+    KillSwitch,
+    /// `FILLMAXLIMIT` bets aren't allowed
+    FillMaxLimit,
+    /// stake {0} isn't a finite positive amount
+    InvalidStake(f64),
+    /// price {0} can't be used to find the amount at risk
+    InvalidPrice(f64),
+    /// amount at risk {stake} is above the max stake {max}
+    MaxStake {
+        /// Amount at risk of the bet
+        stake: f64,
+        /// The limit
+        max: f64,
+    },
+    /// exposure {exposure} on event {event_id} would exceed {max}
+    EventExposure {
+        /// Event id
+        event_id: i64,
+        /// Exposure including the bet
+        exposure: f64,
+        /// The limit
+        max: f64,
+    },
+    /// exposure {exposure} on league {league_id} would exceed {max}
+    LeagueExposure {
+        /// League id
+        league_id: i32,
+        /// Exposure including the bet
+        exposure: f64,
+        /// The limit
+        max: f64,
+    },
+    /// daily exposure {exposure} would exceed {max}
+    DailyExposure {
+        /// Exposure including the bet
+        exposure: f64,
+        /// The limit
+        max: f64,
+    },
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum BetGuardError<E> {
+    /// bet rejected by the guard: {0}
+    Rejected(#[from] GuardRejected),
+    /// client
+    Client(#[source] E),
+}
+
+#[derive(Debug, Default)]
+struct Exposure {
+    events: HashMap<i64, f64>,
+    leagues: HashMap<i32, f64>,
+    day: Option<NaiveDate>,
+    daily: f64,
+}
+
+impl<C> BetGuard<C>
+where
+    C: PinnacleApiClient + Sync,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    /// Wraps the client
+    pub fn new(client: C, config: BetGuardConfig) -> Self {
+        Self {
+            client,
+            config,
+            exposure: Default::default(),
+            kill_switch: Default::default(),
+        }
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// The kill switch of the guard
+    pub fn kill_switch(&self) -> KillSwitch {
+        self.kill_switch.clone()
+    }
+
+    /// Total amount at risk of the bets on the event
+    pub fn event_exposure(&self, event_id: i64) -> f64 {
+        self.lock()
+            .events
+            .get(&event_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Total amount at risk of the bets on the league
+    pub fn league_exposure(&self, league_id: i32) -> f64 {
+        self.lock()
+            .leagues
+            .get(&league_id)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Total amount at risk of the bets today
+    pub fn daily_exposure(&self) -> f64 {
+        let mut exposure = self.lock();
+        exposure.roll_day();
+        exposure.daily
+    }
+
+    /// Places the bet if it's within the limits. The `league_id` of the event is needed for the
+    /// league exposure and the decimal `price` for the amount at risk of `WIN` stakes, as they
+    /// aren't a part of the bet.
+    pub async fn place(
+        &self,
+        bet: &PlaceStraightBet,
+        league_id: i32,
+        price: f64,
+    ) -> Result<BetOutcome, BetGuardError<C::Error>> {
+        let risk = self.amount_at_risk(bet, price)?;
+        self.check(bet, league_id, risk)?;
+        if self.config.dry_run {
+            let body = serde_json::to_string(bet).unwrap_or_default();
+            eprintln!("DRY RUN POST {} {body}", PlaceStraightBet::PATH);
+            return Ok(BetOutcome::DryRun);
+        }
+        let resp = match self.client.post(bet).await {
+            Ok(resp) => resp,
+            Err(e) => {
+                if is_definitive_failure(&e) {
+                    self.lock().add(bet.event_id, league_id, -risk);
+                }
+                return Err(BetGuardError::Client(e));
+            }
+        };
+        let placed_risk = match (&resp.status, &resp.straight_bet) {
+            (PlaceBetStatus::ProcessedWithError, _) => 0.0,
+            (_, Some(placed)) => placed.risk,
+            (_, None) => risk,
+        };
+        self.lock().add(bet.event_id, league_id, placed_risk - risk);
+        Ok(BetOutcome::Placed(Box::new(resp)))
+    }
+
+    /// Amount at risk of the bet, the stake is the amount to win for `WIN` stakes
+    fn amount_at_risk(&self, bet: &PlaceStraightBet, price: f64) -> Result<f64, GuardRejected> {
+        if bet.fill_type == FillType::FillMaxLimit && !self.config.allow_fill_max_limit {
+            return Err(GuardRejected::FillMaxLimit);
+        }
+        // NaN or negative amounts would pass the limits and corrupt the exposure
+        if !(bet.stake.is_finite() && bet.stake > 0.0) {
+            return Err(GuardRejected::InvalidStake(bet.stake));
+        }
+        let risk = match bet.win_risk_stake {
+            WinRiskStake::Risk => bet.stake,
+            WinRiskStake::Win if price.is_finite() && price > 1.0 => bet.stake / (price - 1.0),
+            WinRiskStake::Win => return Err(GuardRejected::InvalidPrice(price)),
+        };
+        if !risk.is_finite() {
+            return Err(GuardRejected::InvalidPrice(price));
+        }
+        Ok(risk)
+    }
+
+    /// Checks the limits, reserving the exposure of the bet
+    fn check(
+        &self,
+        bet: &PlaceStraightBet,
+        league_id: i32,
+        stake: f64,
+    ) -> Result<(), GuardRejected> {
+        if self.kill_switch.is_engaged() {
+            return Err(GuardRejected::KillSwitch);
+        }
+        if let Some(max) = self.config.max_stake {
+            if stake > max {
+                return Err(GuardRejected::MaxStake { stake, max });
+            }
+        }
+
+        let mut exposure = self.lock();
+        exposure.roll_day();
+        if let Some(max) = self.config.max_event_exposure {
+            let event_id = bet.event_id;
+            let total = exposure.events.get(&event_id).copied().unwrap_or_default() + stake;
+            if total > max {
+                return Err(GuardRejected::EventExposure {
+                    event_id,
+                    exposure: total,
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.config.max_league_exposure {
+            let total = exposure
+                .leagues
+                .get(&league_id)
+                .copied()
+                .unwrap_or_default()
+                + stake;
+            if total > max {
+                return Err(GuardRejected::LeagueExposure {
+                    league_id,
+                    exposure: total,
+                    max,
+                });
+            }
+        }
+        if let Some(max) = self.config.max_daily_exposure {
+            let total = exposure.daily + stake;
+            if total > max {
+                return Err(GuardRejected::DailyExposure {
+                    exposure: total,
+                    max,
+                });
+            }
+        }
+        exposure.add(bet.event_id, league_id, stake);
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Exposure> {
+        self.exposure.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Exposure {
+    /// Resets the daily exposure on a new day
+    fn roll_day(&mut self) {
+        let today = Utc::now().date_naive();
+        if self.day != Some(today) {
+            self.day = Some(today);
+            self.daily = 0.0;
+        }
+    }
+
+    fn add(&mut self, event_id: i64, league_id: i32, stake: f64) {
+        *self.events.entry(event_id).or_default() += stake;
+        *self.leagues.entry(league_id).or_default() += stake;
+        self.daily += stake;
+    }
+}
+
+impl KillSwitch {
+    /// Stops all the bets
+    pub fn engage(&self) {
+        self.0.store(true, Ordering::SeqCst)
+    }
+
+    /// Allows the bets again
+    pub fn release(&self) {
+        self.0.store(false, Ordering::SeqCst)
+    }
+
+    /// Whether the bets are stopped
+    pub fn is_engaged(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RawResponse;
+    use crate::mock_client::MockPinnacleClient;
+    use crate::requests::*;
+    use reqwest::Method;
+
+    fn bet(event_id: i64, stake: f64) -> PlaceStraightBet {
+        PlaceStraightBet {
+            odds_format: OddsFormat::Decimal,
+            unique_request_id: "id".into(),
+            accept_better_line: true,
+            stake,
+            win_risk_stake: WinRiskStake::Risk,
+            line_id: 1,
+            alt_line_id: None,
+            fill_type: FillType::Normal,
+            sport_id: 29,
+            event_id,
+            period_number: 0,
+            bet_type: BetType::Moneyline,
+            team: Some(Team::Team1),
+            side: None,
+        }
+    }
+
+    /// Accepts the bets, risking their stake
+    fn accepting_client() -> MockPinnacleClient {
+        let client = MockPinnacleClient::new();
+        client.respond_with(Method::POST, PlaceStraightBet::PATH, |call| {
+            let bet = call.body.clone().unwrap_or_default();
+            let body = serde_json::json!({
+                "status": "ACCEPTED",
+                "uniqueRequestId": "id",
+                "straightBet": {
+                    "betId": 1, "uniqueRequestId": "id", "wagerNumber": 1,
+                    "placedAt": "2024-01-01T00:00:00Z", "betStatus": "ACCEPTED",
+                    "betType": "MONEYLINE", "win": 1, "risk": bet["stake"], "price": 2,
+                    "sportId": 29, "eventId": bet["eventId"], "periodNumber": 0
+                }
+            });
+            Ok(RawResponse {
+                status: 200,
+                headers: Default::default(),
+                body: body.to_string(),
+            })
+        });
+        client
+    }
+
+    #[tokio::test]
+    async fn test_bet_guard() {
+        let config = BetGuardConfig {
+            max_stake: Some(50.0),
+            max_event_exposure: Some(60.0),
+            max_league_exposure: Some(100.0),
+            max_daily_exposure: Some(120.0),
+            ..Default::default()
+        };
+        let guard = BetGuard::new(accepting_client(), config.clone());
+        let rejected = |r: Result<_, BetGuardError<_>>| match r {
+            Err(BetGuardError::Rejected(rule)) => rule,
+            r => panic!("not rejected: {r:?}"),
+        };
+
+        assert!(matches!(
+            guard.place(&bet(1, 40.0), 1, 2.0).await,
+            Ok(BetOutcome::Placed(_))
+        ));
+        assert_eq!(
+            rejected(guard.place(&bet(1, 51.0), 1, 2.0).await),
+            GuardRejected::MaxStake {
+                stake: 51.0,
+                max: 50.0
+            }
+        );
+        let mut to_win = bet(1, 30.0);
+        to_win.win_risk_stake = WinRiskStake::Win;
+        assert_eq!(
+            rejected(guard.place(&to_win, 1, 1.5).await),
+            GuardRejected::MaxStake {
+                stake: 60.0,
+                max: 50.0
+            }
+        );
+        for stake in [f64::NAN, -10.0, 0.0, f64::INFINITY] {
+            assert!(matches!(
+                rejected(guard.place(&bet(1, stake), 1, 2.0).await),
+                GuardRejected::InvalidStake(_)
+            ));
+        }
+        assert_eq!(
+            rejected(guard.place(&to_win, 1, f64::INFINITY).await),
+            GuardRejected::InvalidPrice(f64::INFINITY)
+        );
+        let mut fill_max = bet(1, 1.0);
+        fill_max.fill_type = FillType::FillMaxLimit;
+        assert_eq!(
+            rejected(guard.place(&fill_max, 1, 2.0).await),
+            GuardRejected::FillMaxLimit
+        );
+        assert!(matches!(
+            rejected(guard.place(&bet(1, 30.0), 1, 2.0).await),
+            GuardRejected::EventExposure { event_id: 1, .. }
+        ));
+        guard.place(&bet(2, 50.0), 1, 2.0).await.unwrap();
+        assert!(matches!(
+            rejected(guard.place(&bet(3, 20.0), 1, 2.0).await),
+            GuardRejected::LeagueExposure { league_id: 1, .. }
+        ));
+        guard.place(&bet(3, 20.0), 2, 2.0).await.unwrap();
+        assert!(matches!(
+            rejected(guard.place(&bet(4, 20.0), 3, 2.0).await),
+            GuardRejected::DailyExposure { .. }
+        ));
+        assert_eq!(guard.event_exposure(1), 40.0);
+        assert_eq!(guard.league_exposure(1), 90.0);
+        assert_eq!(guard.daily_exposure(), 110.0);
+
+        // A rejected request releases the exposure, one with unknown outcome keeps it
+        guard.inner().fail(PlaceStraightBet::PATH, 400, "");
+        guard.place(&bet(5, 5.0), 4, 2.0).await.unwrap_err();
+        assert_eq!(guard.event_exposure(5), 0.0);
+        guard.inner().fail(PlaceStraightBet::PATH, 503, "");
+        guard.place(&bet(5, 5.0), 4, 2.0).await.unwrap_err();
+        assert_eq!(guard.event_exposure(5), 5.0);
+
+        guard.kill_switch().engage();
+        assert_eq!(
+            rejected(guard.place(&bet(6, 1.0), 4, 2.0).await),
+            GuardRejected::KillSwitch
+        );
+        guard.kill_switch().release();
+        guard.place(&bet(6, 1.0), 4, 2.0).await.unwrap();
+
+        let dry_run = BetGuard::new(
+            accepting_client(),
+            BetGuardConfig {
+                dry_run: true,
+                ..config
+            },
+        );
+        assert!(matches!(
+            dry_run.place(&bet(1, 40.0), 1, 2.0).await,
+            Ok(BetOutcome::DryRun)
+        ));
+        assert_eq!(dry_run.daily_exposure(), 40.0);
+        // The simulated bets count towards the limits
+        assert!(matches!(
+            rejected(dry_run.place(&bet(1, 30.0), 1, 2.0).await),
+            GuardRejected::EventExposure { event_id: 1, .. }
+        ));
+        dry_run.place(&bet(2, 50.0), 2, 2.0).await.unwrap();
+        assert!(matches!(
+            rejected(dry_run.place(&bet(3, 40.0), 3, 2.0).await),
+            GuardRejected::DailyExposure { .. }
+        ));
+        assert!(dry_run.inner().calls().is_empty());
+    }
+}
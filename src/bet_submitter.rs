@@ -0,0 +1,501 @@
+//! Idempotent bet submission.
+//!
+//! A failed bet placement request leaves us not knowing whether the bet is placed, and
+//! resubmitting it blindly could place it twice. [`BetSubmitter`] gives every bet without one a
+//! fresh `uniqueRequestId`, writes it to a [`BetJournal`] before sending, and unless the request
+//! definitely had no effect, e.g. it was rejected with a 4xx status, polls [`GetBets`] for the
+//! real outcome. So does a response accepting the bet without telling its details. Live bets waiting for the live delay (`PENDING_ACCEPTANCE`) are polled until
+//! they resolve. A bet is only concluded not placed once it's missing from [`GetBets`] for
+//! longer than its `uniqueRequestId` is valid, until then it stays in the journal unresolved.
+//!
+//! Bets are never served from a cache, so the submitter can wrap a
+//! [`PinnacleCachingClient`](crate::caching_client::PinnacleCachingClient). Unique request ids
+//! must be UUIDs, as they name the journal files.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let submitter = BetSubmitter::new(
+//!     PinnacleClient::new("pinnacle_user", "pinnacle_password"),
+//!     "bet-journal",
+//! );
+//! // Settle the bets left unresolved by a previous run
+//! for entry in submitter.resume().await? {
+//!     println!("{} {:?}", entry.bet.unique_request_id, entry.outcome);
+//! }
+//! # let bet = PlaceStraightBet {
+//! #     odds_format: OddsFormat::Decimal,
+//! #     unique_request_id: String::new(),
+//! #     accept_better_line: true,
+//! #     stake: 10.0,
+//! #     win_risk_stake: WinRiskStake::Risk,
+//! #     line_id: 1,
+//! #     alt_line_id: None,
+//! #     fill_type: FillType::Normal,
+//! #     sport_id: 29,
+//! #     event_id: 1,
+//! #     period_number: 0,
+//! #     bet_type: BetType::Moneyline,
+//! #     team: Some(Team::Team1),
+//! #     side: None,
+//! # };
+//! match submitter.submit(bet).await? {
+//!     SubmitOutcome::Placed(bet) => println!("{:?}", bet.bet_status),
+//!     SubmitOutcome::Failed { error_code } => eprintln!("not placed: {error_code:?}"),
+//!     SubmitOutcome::NotPlaced => eprintln!("not placed"),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::client::PinnacleClientError;
+use crate::requests::{GetBets, PlaceStraightBet};
+use crate::responses::{BetStatus, PlaceBetResponse, PlaceBetStatus, StraightBet};
+use crate::traits::PinnacleApiClient;
+use crate::util::{error_chain, is_definitive_failure, parse_json};
+use chrono::{DateTime, Utc};
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// How long Pinnacle uses a unique request id to detect duplicate bets
+const UNIQUE_REQUEST_ID_TTL: chrono::Duration = chrono::Duration::minutes(30);
+
+/// Places bets without the risk of placing them twice
+#[derive(Debug)]
+pub struct BetSubmitter<C> {
+    client: C,
+    journal: BetJournal,
+    poll_interval: Duration,
+    pending_timeout: Duration,
+}
+
+/// Keeps the submitted bets in the `{unique_request_id}.json` files of a folder, ids which aren't
+/// UUIDs are refused
+#[derive(Debug, Clone)]
+pub struct BetJournal {
+    dir: PathBuf,
+}
+
+/// A submitted bet along with its outcome
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JournalEntry {
+    /// The bet as it was sent
+    pub bet: PlaceStraightBet,
+    /// When the bet was submitted
+    pub submitted_at: DateTime<Utc>,
+    /// Outcome of the bet, `None` until it's known
+    pub outcome: Option<SubmitOutcome>,
+}
+
+/// Outcome of a submitted bet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub enum SubmitOutcome {
+    /// The bet is placed, see its status. It's still `PENDING_ACCEPTANCE` if the live delay
+    /// didn't pass in time.
+    Placed(Box<StraightBet>),
+    /// Pinnacle refused the bet
+    Failed {
+        /// Reason, e.g. `LINE_CHANGED`
+        error_code: Option<String>,
+    },
+    /// The request failed and Pinnacle has had no bet with its unique request id for longer
+    /// than the id is valid
+    NotPlaced,
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum BetSubmitError<E = PinnacleClientError> {
+    /// journal
+    Journal(#[from] JournalError),
+    /// client
+    Client(#[source] E),
+    /// outcome of bet {0} is unknown, it's kept in the journal to be resumed
+    Unresolved(String, #[source] Option<Box<E>>),
+}
+
+/// bet journal {1:?}
+#[derive(Debug, Display, Error)]
+pub struct JournalError(#[source] io::Error, PathBuf);
+
+impl SubmitOutcome {
+    /// Whether the outcome won't change anymore
+    pub fn is_resolved(&self) -> bool {
+        !matches!(self, Self::Placed(bet) if bet.bet_status == BetStatus::PendingAcceptance)
+    }
+}
+
+impl<C> BetSubmitter<C>
+where
+    C: PinnacleApiClient + Sync,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    /// Wraps the client, journaling bets in the `journal_dir` folder
+    pub fn new(client: C, journal_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            client,
+            journal: BetJournal::new(journal_dir),
+            poll_interval: Duration::from_secs(2),
+            pending_timeout: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets how often and how long to poll `PENDING_ACCEPTANCE` bets
+    pub fn with_polling(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.poll_interval = interval;
+        self.pending_timeout = timeout;
+        self
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// The journal of submitted bets
+    pub fn journal(&self) -> &BetJournal {
+        &self.journal
+    }
+
+    /// Places the bet, generating its unique request id if it's empty. Resubmitting a bet with
+    /// the same id within 30 minutes doesn't place it twice.
+    pub async fn submit(
+        &self,
+        mut bet: PlaceStraightBet,
+    ) -> Result<SubmitOutcome, BetSubmitError<C::Error>> {
+        if bet.unique_request_id.is_empty() {
+            bet.unique_request_id = Uuid::new_v4().to_string();
+        }
+        let mut entry = JournalEntry {
+            bet,
+            submitted_at: Utc::now(),
+            outcome: None,
+        };
+        self.journal.write(&entry)?;
+
+        let outcome = match self.client.post(&entry.bet).await {
+            Ok(resp) => match outcome_from_response(resp) {
+                Some(outcome) => outcome,
+                None => self.poll_outcome(&entry, None).await?,
+            },
+            Err(e) if is_definitive_failure(&e) => {
+                entry.outcome = Some(SubmitOutcome::NotPlaced);
+                self.journal.write(&entry)?;
+                return Err(BetSubmitError::Client(e));
+            }
+            Err(e) => {
+                eprintln!(
+                    "Placing bet {} failed, recovering its outcome <-- {}",
+                    entry.bet.unique_request_id,
+                    error_chain(&e)
+                );
+                self.poll_outcome(&entry, Some(e)).await?
+            }
+        };
+        self.settle(entry, outcome).await
+    }
+
+    /// Settles the bets of the journal which outcome isn't known yet, e.g. after a crash
+    pub async fn resume(&self) -> Result<Vec<JournalEntry>, BetSubmitError<C::Error>> {
+        let mut settled = Vec::new();
+        for mut entry in self.journal.unresolved()? {
+            let outcome = self.poll_outcome(&entry, None).await?;
+            entry.outcome = Some(self.settle(entry.clone(), outcome).await?);
+            settled.push(entry);
+        }
+        Ok(settled)
+    }
+
+    /// Looks up a bet by its unique request id, `None` if Pinnacle doesn't have it (yet)
+    pub async fn recover(&self, unique_request_id: &str) -> Result<Option<StraightBet>, C::Error> {
+        let req = GetBets {
+            unique_request_ids: Some(vec![unique_request_id.into()]),
+            ..Default::default()
+        };
+        let resp = self.client.get(&req).await?;
+        Ok(resp
+            .straight_bets
+            .into_iter()
+            .find(|b| b.unique_request_id.as_deref() == Some(unique_request_id)))
+    }
+
+    /// Records the outcome, polling a `PENDING_ACCEPTANCE` bet until it resolves or the timeout
+    /// passes
+    async fn settle(
+        &self,
+        mut entry: JournalEntry,
+        mut outcome: SubmitOutcome,
+    ) -> Result<SubmitOutcome, BetSubmitError<C::Error>> {
+        let deadline = Instant::now() + self.pending_timeout;
+        loop {
+            entry.outcome = Some(outcome.clone());
+            self.journal.write(&entry)?;
+            if outcome.is_resolved() || Instant::now() >= deadline {
+                return Ok(outcome);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+            outcome = self.poll_outcome(&entry, None).await?;
+        }
+    }
+
+    /// Polls the bet until Pinnacle has it or the pending timeout passes. A missing bet is only
+    /// concluded not placed when its unique request id has expired, otherwise it's unresolved.
+    async fn poll_outcome(
+        &self,
+        entry: &JournalEntry,
+        mut error: Option<C::Error>,
+    ) -> Result<SubmitOutcome, BetSubmitError<C::Error>> {
+        let id = &entry.bet.unique_request_id;
+        let deadline = Instant::now() + self.pending_timeout;
+        loop {
+            match self.recover(id).await {
+                Ok(Some(bet)) => return Ok(SubmitOutcome::Placed(Box::new(bet))),
+                Ok(None) if Utc::now() - entry.submitted_at > UNIQUE_REQUEST_ID_TTL => {
+                    return Ok(SubmitOutcome::NotPlaced)
+                }
+                Ok(None) => (),
+                Err(e) => error = Some(e),
+            }
+            if Instant::now() >= deadline {
+                return Err(BetSubmitError::Unresolved(id.clone(), error.map(Box::new)));
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+}
+
+impl BetJournal {
+    /// Creates a journal in the `dir` folder
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Entries which outcome isn't known or may still change
+    pub fn unresolved(&self) -> Result<Vec<JournalEntry>, JournalError> {
+        let err = |e| JournalError(e, self.dir.clone());
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(err(e)),
+        };
+        let mut entries = Vec::new();
+        for file in dir {
+            let path = file.map_err(err)?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let entry = read_entry(&path)?;
+                if !entry
+                    .outcome
+                    .as_ref()
+                    .is_some_and(SubmitOutcome::is_resolved)
+                {
+                    entries.push(entry);
+                }
+            }
+        }
+        entries.sort_by_key(|e| e.submitted_at);
+        Ok(entries)
+    }
+
+    /// Reads the entry of a bet
+    pub fn get(&self, unique_request_id: &str) -> Result<JournalEntry, JournalError> {
+        read_entry(&self.path(unique_request_id)?)
+    }
+
+    /// Path of the entry, only UUIDs are accepted so an id can't point outside the folder
+    fn path(&self, unique_request_id: &str) -> Result<PathBuf, JournalError> {
+        let path = self.dir.join(format!("{unique_request_id}.json"));
+        match Uuid::parse_str(unique_request_id) {
+            Ok(_) => Ok(path),
+            Err(e) => Err(JournalError(
+                io::Error::new(io::ErrorKind::InvalidInput, e),
+                path,
+            )),
+        }
+    }
+
+    /// Writes the entry atomically, so a crash can't leave it half written
+    fn write(&self, entry: &JournalEntry) -> Result<(), JournalError> {
+        let path = self.path(&entry.bet.unique_request_id)?;
+        let err = |e| JournalError(e, path.clone());
+        fs::create_dir_all(&self.dir).map_err(err)?;
+        let content = serde_json::to_string_pretty(entry).map_err(|e| err(e.into()))?;
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content).map_err(err)?;
+        fs::rename(&tmp, &path).map_err(err)
+    }
+}
+
+fn read_entry(path: &Path) -> Result<JournalEntry, JournalError> {
+    let err = |e| JournalError(e, path.into());
+    let content = fs::read_to_string(path).map_err(err)?;
+    parse_json(&content).map_err(|e| err(io::Error::new(io::ErrorKind::InvalidData, e)))
+}
+
+/// Outcome told by the response, `None` if the bet is accepted without its details
+fn outcome_from_response(resp: PlaceBetResponse) -> Option<SubmitOutcome> {
+    match (resp.status, resp.straight_bet) {
+        (PlaceBetStatus::ProcessedWithError, _) => Some(SubmitOutcome::Failed {
+            error_code: resp.error_code,
+        }),
+        (_, Some(bet)) => Some(SubmitOutcome::Placed(Box::new(bet))),
+        (_, None) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::RawResponse;
+    use crate::requests::*;
+    use async_trait::async_trait;
+    use reqwest::IntoUrl;
+    use serde::de::DeserializeOwned;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Fails placing bets with 503, but places them. The first check doesn't find the bet, the
+    /// second one finds it pending and the third accepted. Bets of the event 2 are rejected
+    /// with 400 and bets of the event 3 are accepted without their details.
+    #[derive(Default)]
+    struct FlakyClient {
+        checks: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PinnacleApiClient for FlakyClient {
+        type Error = PinnacleClientError;
+
+        async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+        where
+            U: IntoUrl + Send,
+            T: DeserializeOwned + Serialize + Send,
+        {
+            let url = url.into_url()?;
+            let id = url.query_pairs().find(|(k, _)| k == "uniqueRequestIds");
+            let id = id.map(|(_, v)| v.to_string()).unwrap_or_default();
+            let status = match self.checks.fetch_add(1, Ordering::SeqCst) {
+                0 => None,
+                1 => Some("PENDING_ACCEPTANCE"),
+                _ => Some("ACCEPTED"),
+            };
+            let body = match status {
+                None => r#"{"straightBets": []}"#.into(),
+                Some(status) => format!(
+                    r#"{{"straightBets": [{{"betId": 1, "uniqueRequestId": "{id}",
+                    "wagerNumber": 1, "placedAt": "2024-01-01T00:00:00Z",
+                    "betStatus": "{status}", "betType": "MONEYLINE", "win": 1.5, "risk": 1,
+                    "price": 2.5, "sportId": 29, "eventId": 1, "periodNumber": 0}}]}}"#
+                ),
+            };
+            RawResponse {
+                status: 200,
+                headers: Default::default(),
+                body,
+            }
+            .parse(&url)
+        }
+
+        async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+        where
+            U: IntoUrl + Send,
+            B: Serialize + Sync,
+            T: DeserializeOwned + Serialize + Send,
+        {
+            let url = url.into_url()?;
+            let body = serde_json::to_value(body).unwrap();
+            let status = match body["eventId"].as_i64() {
+                Some(2) => 400,
+                Some(3) => {
+                    let id = &body["uniqueRequestId"];
+                    let body = format!(r#"{{"status": "ACCEPTED", "uniqueRequestId": {id}}}"#);
+                    return RawResponse {
+                        status: 200,
+                        headers: Default::default(),
+                        body,
+                    }
+                    .parse(&url);
+                }
+                _ => 503,
+            };
+            Err(PinnacleClientError::HttpStatus(status, url, "".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submit_recovers_timed_out_bet() {
+        let dir = std::env::temp_dir().join(format!("pinnacle-journal-{}", std::process::id()));
+        let submitter = BetSubmitter::new(FlakyClient::default(), &dir)
+            .with_polling(Duration::from_millis(1), Duration::from_secs(1));
+        let id = "6f2b3c1e-8a4d-4e5f-9b0a-1c2d3e4f5a6b";
+        let bet = PlaceStraightBet {
+            odds_format: OddsFormat::Decimal,
+            unique_request_id: id.into(),
+            accept_better_line: true,
+            stake: 1.0,
+            win_risk_stake: WinRiskStake::Risk,
+            line_id: 1,
+            alt_line_id: None,
+            fill_type: FillType::Normal,
+            sport_id: 29,
+            event_id: 1,
+            period_number: 0,
+            bet_type: BetType::Moneyline,
+            team: Some(Team::Team1),
+            side: None,
+        };
+
+        let outcome = submitter.submit(bet.clone()).await.unwrap();
+        let SubmitOutcome::Placed(placed) = outcome else {
+            panic!("not placed: {outcome:?}");
+        };
+        assert_eq!(placed.bet_status, BetStatus::Accepted);
+        assert_eq!(submitter.inner().checks.load(Ordering::SeqCst), 3);
+        assert_eq!(placed.unique_request_id.as_deref(), Some(id));
+        let entry = submitter.journal().get(id).unwrap();
+        assert!(entry.outcome.unwrap().is_resolved());
+        assert!(submitter.journal().unresolved().unwrap().is_empty());
+
+        // A rejected bet isn't looked up
+        let rejected = PlaceStraightBet {
+            unique_request_id: "".into(),
+            event_id: 2,
+            ..bet.clone()
+        };
+        let err = submitter.submit(rejected).await.unwrap_err();
+        assert!(matches!(
+            err,
+            BetSubmitError::Client(PinnacleClientError::HttpStatus(400, ..))
+        ));
+        assert_eq!(submitter.inner().checks.load(Ordering::SeqCst), 3);
+        assert!(submitter.journal().unresolved().unwrap().is_empty());
+
+        // A bet accepted without its details is looked up
+        let accepted = PlaceStraightBet {
+            unique_request_id: "".into(),
+            event_id: 3,
+            ..bet.clone()
+        };
+        let outcome = submitter.submit(accepted).await.unwrap();
+        assert!(matches!(outcome, SubmitOutcome::Placed(_)), "{outcome:?}");
+        assert_eq!(submitter.inner().checks.load(Ordering::SeqCst), 4);
+
+        // An id which isn't a UUID can't name a journal file
+        let escaping = PlaceStraightBet {
+            unique_request_id: "../escaping".into(),
+            ..bet
+        };
+        let err = submitter.submit(escaping).await.unwrap_err();
+        assert!(matches!(err, BetSubmitError::Journal(_)), "{err:?}");
+        assert!(submitter.journal().get("../escaping").is_err());
+        assert_eq!(submitter.inner().checks.load(Ordering::SeqCst), 4);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,50 @@
+//! Mock Pinnacle API server, requires the `mock-server` feature.
+//!
+//! Serves json files of the fixtures folder named after the endpoint paths, e.g.
+//! `fixtures/v1/odds.json` for `/v1/odds`, and accepts placed bets.
+use clap::Parser;
+use pinnacle::prelude::*;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+
+    /// Folder with the responses
+    #[arg(long)]
+    fixtures: Option<PathBuf>,
+
+    /// Required basic auth username
+    #[arg(env, long, requires = "pinnacle_password")]
+    pinnacle_username: Option<String>,
+
+    /// Required basic auth password
+    #[arg(env, long, hide_env_values = true)]
+    pinnacle_password: Option<String>,
+
+    /// Reject placed bets with the error code, e.g. LINE_CHANGED
+    #[arg(long)]
+    reject_bets: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), MockServerError> {
+    let cli = Cli::parse();
+    let server = MockServer::start_on(cli.addr)?;
+    if let Some(dir) = &cli.fixtures {
+        server.load_dir(dir)?;
+    }
+    if let (Some(username), Some(password)) = (&cli.pinnacle_username, &cli.pinnacle_password) {
+        server.require_auth(username, password);
+    }
+    if let Some(error_code) = &cli.reject_bets {
+        server.reject_bets(error_code);
+    }
+    eprintln!("Listening on {}", server.origin());
+    std::future::pending::<()>().await;
+    Ok(())
+}
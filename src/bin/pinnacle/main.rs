@@ -0,0 +1,462 @@
+//! Command line client of Pinnacle API, requires the `cli` feature.
+//!
+//! Exit codes: 0 - success, 1 - error, 2 - wrong arguments, 3 - unsuccessful HTTP status.
+use clap::{Parser, Subcommand, ValueEnum};
+use pinnacle::prelude::*;
+use pinnacle::util::error_chain;
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+mod watch;
+
+type Result<T, E = Box<dyn Error>> = std::result::Result<T, E>;
+
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Cli {
+    #[arg(env, long)]
+    pinnacle_username: String,
+
+    #[arg(env, long, hide_env_values = true)]
+    pinnacle_password: String,
+
+    /// Output format
+    #[arg(long, short, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    /// Cache responses in the folder
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+
+    /// How long cached responses are fresh, in seconds
+    #[arg(long, default_value_t = 60, requires = "cache_dir")]
+    cache_ttl: u64,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Output {
+    /// The response as it is
+    Json,
+    /// A row per item of the response
+    Table,
+    /// A row per item of the response
+    Csv,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Returns current balance
+    Balance,
+    /// Returns sports
+    Sports,
+    /// Leagues in a particular sport
+    Leagues { sport_id: i32 },
+    /// Periods in a particular sport
+    Periods { sport_id: i32 },
+    /// Fixtures for a particular sport
+    Fixtures {
+        sport_id: i32,
+        #[arg(long, value_delimiter = ',')]
+        league_ids: Option<Vec<i32>>,
+        #[arg(long, value_delimiter = ',')]
+        event_ids: Option<Vec<i32>>,
+        /// Only live events
+        #[arg(long)]
+        live: bool,
+        #[arg(long)]
+        since: Option<i64>,
+    },
+    /// Fixtures settled in the last 24 hours
+    Settled {
+        sport_id: i32,
+        #[arg(long, value_delimiter = ',')]
+        league_ids: Option<Vec<i32>>,
+        #[arg(long)]
+        since: Option<i64>,
+    },
+    /// Straight odds for a particular sport
+    Odds {
+        sport_id: i32,
+        #[arg(long, value_delimiter = ',')]
+        league_ids: Option<Vec<i32>>,
+        #[arg(long, value_delimiter = ',')]
+        event_ids: Option<Vec<i64>>,
+        /// Only live odds
+        #[arg(long)]
+        live: bool,
+        /// american, decimal, hongkong, indonesian or malay
+        #[arg(long, value_parser = serde_enum::<OddsFormat>)]
+        odds_format: Option<OddsFormat>,
+        #[arg(long)]
+        since: Option<i64>,
+        /// Currency of the limits, USD by default
+        #[arg(long)]
+        to_currency_code: Option<String>,
+    },
+    /// Latest line of a selection
+    Line {
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// american, decimal, hongkong, indonesian or malay
+        #[arg(long, value_parser = serde_enum::<OddsFormat>, default_value = "decimal")]
+        odds_format: OddsFormat,
+    },
+    /// Bets by ids or unique request ids
+    Bets {
+        #[arg(long, value_delimiter = ',')]
+        bet_ids: Option<Vec<i64>>,
+        #[arg(long, value_delimiter = ',')]
+        unique_request_ids: Option<Vec<String>>,
+    },
+    /// Live odds board, highlighting price moves since the previous poll
+    Watch(watch::WatchArgs),
+    /// Places a straight bet at the latest line, only prints the bet without `--yes`
+    PlaceBet {
+        #[command(flatten)]
+        selection: SelectionArgs,
+        /// Amount to risk
+        #[arg(long)]
+        stake: f64,
+        /// normal, fillandkill or fillmaxlimit
+        #[arg(long, value_parser = serde_enum::<FillType>, default_value = "normal")]
+        fill_type: FillType,
+        /// Really place the bet
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(clap::Args)]
+struct SelectionArgs {
+    #[arg(long)]
+    sport_id: i32,
+    #[arg(long)]
+    league_id: i32,
+    #[arg(long)]
+    event_id: i64,
+    #[arg(long, default_value_t = 0)]
+    period_number: i32,
+    /// moneyline, spread, total_points or team_total_points
+    #[arg(long, value_parser = serde_enum::<BetType>)]
+    bet_type: BetType,
+    /// team1, team2 or draw
+    #[arg(long, value_parser = serde_enum::<Team>)]
+    team: Option<Team>,
+    /// over or under
+    #[arg(long, value_parser = serde_enum::<BetSide>)]
+    side: Option<BetSide>,
+    #[arg(long, allow_hyphen_values = true)]
+    handicap: Option<f64>,
+}
+
+/// A price of the odds
+#[derive(Serialize)]
+struct OddsPriceRow {
+    league_id: i32,
+    event_id: i64,
+    period_number: i32,
+    line_id: i64,
+    cutoff: chrono::DateTime<chrono::Utc>,
+    status: i32,
+    market_type: MarketType,
+    side: Side,
+    line: Option<f64>,
+    price: f64,
+    alt_line_id: Option<i64>,
+    max: Option<f64>,
+    updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// A settled period
+#[derive(Serialize)]
+struct SettledRow {
+    league_id: i32,
+    event_id: i64,
+    period_number: i32,
+    status: i32,
+    settlement_id: i64,
+    settled_at: chrono::DateTime<chrono::Utc>,
+    team1_score: Option<f64>,
+    team2_score: Option<f64>,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenvy::dotenv().ok();
+    let cli = Cli::parse();
+    let result = match &cli.cache_dir {
+        Some(dir) => {
+            let client = PinnacleCachingClient::new(
+                &cli.pinnacle_username,
+                &cli.pinnacle_password,
+                dir,
+                Duration::from_secs(cli.cache_ttl),
+            );
+            run(&client, &cli).await
+        }
+        None => {
+            let client = PinnacleClient::new(&cli.pinnacle_username, &cli.pinnacle_password);
+            run(&client, &cli).await
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", error_chain(e.as_ref()));
+            match e.downcast_ref() {
+                Some(PinnacleClientError::HttpStatus(..)) => ExitCode::from(3),
+                _ => ExitCode::FAILURE,
+            }
+        }
+    }
+}
+
+async fn run<C>(client: &C, cli: &Cli) -> Result<()>
+where
+    C: PinnacleApiClient<Error = PinnacleClientError> + Sync,
+{
+    let output = cli.output;
+    match &cli.command {
+        Command::Balance => {
+            let resp = client.get(&GetClientBalance).await?;
+            print(output, &resp, std::slice::from_ref(&resp))
+        }
+        Command::Sports => {
+            let resp = client.get(&GetSports).await?;
+            print(output, &resp, &resp.sports)
+        }
+        Command::Leagues { sport_id } => {
+            let resp = client
+                .get(&GetLeagues {
+                    sport_id: *sport_id,
+                })
+                .await?;
+            print(output, &resp, &resp.leagues)
+        }
+        Command::Periods { sport_id } => {
+            let resp = client
+                .get(&GetPeriods {
+                    sport_id: *sport_id,
+                })
+                .await?;
+            print(output, &resp, &resp.periods)
+        }
+        Command::Fixtures {
+            sport_id,
+            league_ids,
+            event_ids,
+            live,
+            since,
+        } => {
+            let req = GetFixtures {
+                sport_id: *sport_id,
+                league_ids: league_ids.clone(),
+                is_live: live.then_some(true),
+                since: *since,
+                event_ids: event_ids.clone(),
+            };
+            let resp = client.get(&req).await?;
+            print(output, &resp, &FixtureRow::from_fixtures(&resp))
+        }
+        Command::Settled {
+            sport_id,
+            league_ids,
+            since,
+        } => {
+            let req = GetSettledFixtures {
+                sport_id: *sport_id,
+                league_ids: league_ids.clone(),
+                since: *since,
+            };
+            let resp = client.get(&req).await?;
+            let mut rows = Vec::new();
+            for league in resp.leagues.iter() {
+                for event in league.events.iter() {
+                    for p in event.periods.iter() {
+                        rows.push(SettledRow {
+                            league_id: league.id,
+                            event_id: event.id,
+                            period_number: p.number,
+                            status: p.status,
+                            settlement_id: p.settlement_id,
+                            settled_at: p.settled_at,
+                            team1_score: p.team1_score,
+                            team2_score: p.team2_score,
+                        });
+                    }
+                }
+            }
+            print(output, &resp, &rows)
+        }
+        Command::Odds {
+            sport_id,
+            league_ids,
+            event_ids,
+            live,
+            odds_format,
+            since,
+            to_currency_code,
+        } => {
+            let req = GetStraightOdds {
+                sport_id: *sport_id,
+                league_ids: league_ids.clone(),
+                odds_format: *odds_format,
+                since: *since,
+                is_live: *live,
+                event_ids: event_ids.clone(),
+                to_currency_code: to_currency_code.clone(),
+            };
+            let resp = client.get(&req).await?;
+            let mut rows = Vec::new();
+            for league in resp.leagues.iter() {
+                for event in league.events.iter() {
+                    for period in event.periods.iter() {
+                        for price in period.prices() {
+                            rows.push(OddsPriceRow {
+                                league_id: league.id,
+                                event_id: event.id,
+                                period_number: period.number,
+                                line_id: period.line_id,
+                                cutoff: period.cutoff,
+                                status: period.status,
+                                market_type: price.market_type,
+                                side: price.side,
+                                line: price.line,
+                                price: price.price,
+                                alt_line_id: price.alt_line_id,
+                                max: price.max,
+                                updated_at: price.updated_at,
+                            });
+                        }
+                    }
+                }
+            }
+            print(output, &resp, &rows)
+        }
+        Command::Line {
+            selection,
+            odds_format,
+        } => {
+            let resp = client.get(&selection.line_request(*odds_format)).await?;
+            print(output, &resp, std::slice::from_ref(&resp))
+        }
+        Command::Bets {
+            bet_ids,
+            unique_request_ids,
+        } => {
+            let req = GetBets {
+                bet_ids: bet_ids.clone(),
+                unique_request_ids: unique_request_ids.clone(),
+            };
+            let resp = client.get(&req).await?;
+            print(output, &resp, &resp.straight_bets)
+        }
+        Command::Watch(args) => Ok(watch::watch(client, args).await?),
+        Command::PlaceBet {
+            selection,
+            stake,
+            fill_type,
+            yes,
+        } => {
+            let line = client
+                .get(&selection.line_request(OddsFormat::Decimal))
+                .await?;
+            let (LineStatus::Success, Some(line_id)) = (line.status, line.line_id) else {
+                return Err("the line isn't available".into());
+            };
+            let args = selection;
+            let bet = PlaceStraightBet {
+                odds_format: OddsFormat::Decimal,
+                unique_request_id: uuid::Uuid::new_v4().to_string(),
+                accept_better_line: true,
+                stake: *stake,
+                win_risk_stake: WinRiskStake::Risk,
+                line_id,
+                alt_line_id: line.alt_line_id,
+                fill_type: *fill_type,
+                sport_id: args.sport_id,
+                event_id: args.event_id,
+                period_number: args.period_number,
+                bet_type: args.bet_type,
+                team: args.team,
+                side: args.side,
+            };
+            if !yes {
+                eprintln!("Not placed without --yes, price {:?}", line.price);
+                return print(output, &bet, std::slice::from_ref(&bet));
+            }
+            let resp = client.post(&bet).await?;
+            print(output, &resp, resp.straight_bet.as_slice())
+        }
+    }
+}
+
+impl SelectionArgs {
+    fn line_request(&self, odds_format: OddsFormat) -> GetLine {
+        GetLine {
+            league_id: self.league_id,
+            handicap: self.handicap,
+            odds_format,
+            sport_id: self.sport_id,
+            event_id: self.event_id,
+            period_number: self.period_number,
+            bet_type: self.bet_type,
+            team: self.team,
+            side: self.side,
+        }
+    }
+}
+
+/// Prints the whole response as JSON, or the rows as a table or CSV
+fn print<T: Serialize, R: Serialize>(output: Output, response: &T, rows: &[R]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    if let Output::Json = output {
+        serde_json::to_writer_pretty(&mut stdout, response)?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    let csv = writer.into_inner().map_err(|e| e.into_error())?;
+    if let Output::Csv = output {
+        stdout.write_all(&csv)?;
+        return Ok(());
+    }
+
+    let records = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv.as_slice())
+        .into_records()
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut widths = Vec::new();
+    for record in records.iter() {
+        widths.resize(widths.len().max(record.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(record.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    for record in records.iter() {
+        let cells: Vec<_> = record
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+        writeln!(stdout, "{}", cells.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+/// Parses an API enum case-insensitively, e.g. `team_total_points` into `TEAM_TOTAL_POINTS`
+fn serde_enum<T: DeserializeOwned>(s: &str) -> Result<T, String> {
+    let value = serde_json::Value::String(s.to_uppercase().replace('-', "_"));
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
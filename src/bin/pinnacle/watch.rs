@@ -0,0 +1,248 @@
+//! Live odds board refreshing in the terminal
+use chrono::{DateTime, Utc};
+use pinnacle::prelude::*;
+use pinnacle::util::{error_chain, parse_starts};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::Duration;
+
+const RESET: &str = "\x1b[0m";
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const DIM: &str = "\x1b[2m";
+
+#[derive(clap::Args)]
+pub struct WatchArgs {
+    /// Sport id
+    #[arg(long)]
+    sport: i32,
+    /// League ids
+    #[arg(long, value_delimiter = ',')]
+    league: Option<Vec<i32>>,
+    /// Period number
+    #[arg(long, default_value_t = 0)]
+    period: i32,
+    /// Polling interval in seconds
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+}
+
+/// How a price changed since the previous tick
+#[derive(Clone, Copy)]
+enum Move {
+    Up,
+    Down,
+    Line,
+}
+
+struct Fixture {
+    league: String,
+    home: String,
+    away: String,
+    starts: DateTime<Utc>,
+}
+
+/// Fixtures and odds of the board
+#[derive(Default)]
+struct Board {
+    tracker: OddsTracker,
+    fixtures: HashMap<i64, Fixture>,
+    fixtures_last: Option<i64>,
+}
+
+/// Polls odds and fixtures with `since` and redraws the board after each poll. Failed polls
+/// are logged and retried on the next tick.
+pub async fn watch<C>(client: &C, args: &WatchArgs) -> Result<(), PinnacleClientError>
+where
+    C: PinnacleApiClient<Error = PinnacleClientError> + Sync,
+{
+    let mut board = Board::default();
+    loop {
+        match board.poll(client, args).await {
+            Ok(changes) => {
+                board.prune(Utc::now());
+                let moves = moves(&changes, args.period);
+                render(&board.tracker, &board.fixtures, &moves, args.period).ok();
+            }
+            Err(e) => eprintln!("Polling odds failed: {}", error_chain(&e)),
+        }
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+    }
+}
+
+impl Board {
+    /// Merges the fixtures and odds changed since the previous poll
+    async fn poll<C>(
+        &mut self,
+        client: &C,
+        args: &WatchArgs,
+    ) -> Result<Vec<OddsChange>, PinnacleClientError>
+    where
+        C: PinnacleApiClient<Error = PinnacleClientError> + Sync,
+    {
+        let req = GetFixtures {
+            sport_id: args.sport,
+            league_ids: args.league.clone(),
+            since: self.fixtures_last,
+            ..Default::default()
+        };
+        let resp = client.get(&req).await?;
+        self.fixtures_last = Some(resp.last);
+        for league in resp.league {
+            for f in league.events {
+                let Some(starts) = parse_starts(&f.starts) else {
+                    continue;
+                };
+                let fixture = Fixture {
+                    league: league.name.clone(),
+                    home: f.home,
+                    away: f.away,
+                    starts,
+                };
+                self.fixtures.insert(f.id, fixture);
+            }
+        }
+
+        let req = GetStraightOdds {
+            sport_id: args.sport,
+            league_ids: args.league.clone(),
+            odds_format: Some(OddsFormat::Decimal),
+            since: self.tracker.last(),
+            ..Default::default()
+        };
+        Ok(self.tracker.update(client.get(&req).await?))
+    }
+
+    /// Forgets the periods past their cut-off and the started events without any periods left
+    fn prune(&mut self, now: DateTime<Utc>) {
+        self.tracker.remove_cut_off(now);
+        let open: HashSet<i64> = self.tracker.periods().map(|(_, id, _)| id).collect();
+        self.fixtures
+            .retain(|id, f| f.starts > now || open.contains(id));
+    }
+}
+
+fn moves(changes: &[OddsChange], period: i32) -> HashMap<(i64, MarketType, Side), Move> {
+    let mut moves = HashMap::new();
+    for change in changes.iter().filter(|c| c.period_number == period) {
+        let (market_type, side, m) = match change.kind {
+            ChangeKind::PriceMoved {
+                market_type,
+                side,
+                alt_line_id: None,
+                from,
+                to,
+                ..
+            } => (
+                market_type,
+                side,
+                if to > from { Move::Up } else { Move::Down },
+            ),
+            ChangeKind::LineMoved {
+                market_type,
+                side,
+                alt_line_id: None,
+                ..
+            } => (market_type, side, Move::Line),
+            _ => continue,
+        };
+        moves.insert((change.event_id, market_type, side), m);
+    }
+    moves
+}
+
+fn render(
+    tracker: &OddsTracker,
+    fixtures: &HashMap<i64, Fixture>,
+    moves: &HashMap<(i64, MarketType, Side), Move>,
+    period: i32,
+) -> io::Result<()> {
+    let mut rows: Vec<_> = tracker
+        .periods()
+        .filter(|(_, _, p)| p.number == period)
+        .filter_map(|(_, event_id, p)| Some((fixtures.get(&event_id)?, event_id, p)))
+        .collect();
+    rows.sort_by(|a, b| (a.0.starts, &a.0.league, a.1).cmp(&(b.0.starts, &b.0.league, b.1)));
+
+    let mut out = io::stdout().lock();
+    // Clear the screen and move the cursor home
+    write!(out, "\x1b[2J\x1b[H")?;
+    writeln!(
+        out,
+        "{:<11} {:<20} {:<20} {:<20} {:>6} {:>6} {:>6}  {:>6} {:>6} {:>6}  {:>5} {:>6} {:>6}",
+        "Starts",
+        "League",
+        "Home",
+        "Away",
+        "1",
+        "X",
+        "2",
+        "Hdp",
+        "Home",
+        "Away",
+        "Total",
+        "Over",
+        "Under"
+    )?;
+    for (fixture, event_id, p) in rows {
+        let prices = p.prices();
+        let find = |market_type, side| {
+            prices
+                .iter()
+                .find(|x| x.market_type == market_type && x.side == side && x.alt_line_id.is_none())
+        };
+        let cell = |market_type, side, width: usize| {
+            let Some(price) = find(market_type, side) else {
+                return " ".repeat(width);
+            };
+            let text = format!("{:>width$.3}", price.price);
+            match moves.get(&(event_id, market_type, side)) {
+                Some(Move::Up) => format!("{GREEN}{text}{RESET}"),
+                Some(Move::Down) => format!("{RED}{text}{RESET}"),
+                Some(Move::Line) => format!("{YELLOW}{text}{RESET}"),
+                None => text,
+            }
+        };
+        let line =
+            |market_type, side, width: usize| match find(market_type, side).and_then(|x| x.line) {
+                Some(line) => format!("{line:>width$}"),
+                None => " ".repeat(width),
+            };
+        use MarketType::*;
+        use Side::*;
+        let row = format!(
+            "{:<11} {:<20} {:<20} {:<20} {} {} {}  {} {} {}  {} {} {}",
+            fixture.starts.format("%d %b %H:%M"),
+            truncate(&fixture.league, 20),
+            truncate(&fixture.home, 20),
+            truncate(&fixture.away, 20),
+            cell(Moneyline, Home, 6),
+            cell(Moneyline, Draw, 6),
+            cell(Moneyline, Away, 6),
+            line(Spread, Home, 6),
+            cell(Spread, Home, 6),
+            cell(Spread, Away, 6),
+            line(Total, Over, 5),
+            cell(Total, Over, 6),
+            cell(Total, Under, 6),
+        );
+        if p.status == 1 {
+            writeln!(out, "{row}")?;
+        } else {
+            // Colors of the price moves would reset the dimming, so they're dropped
+            writeln!(out, "{DIM}{} offline{RESET}", strip_colors(&row))?;
+        }
+    }
+    out.flush()
+}
+
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
+
+fn strip_colors(s: &str) -> String {
+    [GREEN, RED, YELLOW, RESET]
+        .iter()
+        .fold(s.to_string(), |s, code| s.replace(code, ""))
+}
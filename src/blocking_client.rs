@@ -0,0 +1,397 @@
+//! Synchronous Pinnacle API clients on top of [`reqwest::blocking`], requires the `blocking`
+//! feature.
+//!
+//! The clients mirror [`PinnacleClient`](crate::client::PinnacleClient) and
+//! [`PinnacleCachingClient`](crate::caching_client::PinnacleCachingClient) for code without an
+//! async runtime. They must not be used within one, as [`reqwest::blocking`] panics there.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//! use std::time::Duration;
+//!
+//! # fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleBlockingClient::new("pinnacle_user", "pinnacle_password");
+//! let odds = client.get(&GetStraightOdds {
+//!     sport_id: 29,
+//!     ..Default::default()
+//! })?;
+//!
+//! let client = PinnacleBlockingCachingClient::new(
+//!     "pinnacle_user",
+//!     "pinnacle_password",
+//!     "cache-folder",
+//!     Duration::from_secs(60),
+//! );
+//! let sports = client.get(&GetSports)?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::{CacheEntry, CacheStore, FsCacheStore};
+use crate::caching_client::{from_json, CacheMode, CachePolicy, Freshness, ResponseCache};
+use crate::client::{rebase, PinnacleClientError, RawResponse};
+use crate::traits::{post_request_url, request_url, PinnacleApiPostRequest, PinnacleApiRequest};
+use reqwest::{IntoUrl, Url};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Synchronous API client
+pub trait PinnacleBlockingApiClient {
+    /// The client error
+    type Error: Error;
+
+    /// General GET request using full URL
+    fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        T: DeserializeOwned + Serialize;
+
+    /// GET request returning the response body undecoded
+    fn get_raw<U: IntoUrl>(&self, url: U) -> Result<RawResponse, Self::Error>;
+
+    /// General POST request using full URL, the body is sent as json
+    fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        B: Serialize,
+        T: DeserializeOwned + Serialize;
+
+    /// Typed GET request
+    fn get<Q>(&self, query: &Q) -> Result<Q::Response, Self::Error>
+    where
+        Q: PinnacleApiRequest + Serialize,
+    {
+        self.get_by_url(request_url(query))
+    }
+
+    /// Typed POST request
+    fn post<Q>(&self, body: &Q) -> Result<Q::Response, Self::Error>
+    where
+        Q: PinnacleApiPostRequest + Serialize,
+    {
+        self.post_by_url(post_request_url::<Q>(), body)
+    }
+}
+
+/// Synchronous Pinnacle API client
+#[derive(Debug, Clone)]
+pub struct PinnacleBlockingClient {
+    username: String,
+    password: String,
+    origin: Option<Url>,
+    reqwest_client: reqwest::blocking::Client,
+}
+
+impl PinnacleBlockingClient {
+    /// Creates a new client
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+            origin: None,
+            reqwest_client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Sends the requests to the `origin` instead of the API, e.g. to a local mock server
+    pub fn with_origin(mut self, origin: Url) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    fn send(
+        &self,
+        req: reqwest::blocking::RequestBuilder,
+    ) -> Result<RawResponse, PinnacleClientError> {
+        let req = req
+            .basic_auth(&self.username, Some(&self.password))
+            .build()?;
+        #[cfg(feature = "metrics")]
+        let (method, url, started) = (
+            req.method().clone(),
+            req.url().clone(),
+            std::time::Instant::now(),
+        );
+        let result = self.execute(req);
+        #[cfg(feature = "metrics")]
+        crate::client_metrics::record_request(
+            method.as_str(),
+            &url,
+            result.as_ref().ok().map(|raw| raw.status),
+            started.elapsed(),
+        );
+        result
+    }
+
+    fn execute(&self, req: reqwest::blocking::Request) -> Result<RawResponse, PinnacleClientError> {
+        let resp = self.reqwest_client.execute(req)?;
+        let status = resp.status().as_u16();
+        let headers = resp
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+            .collect();
+        let body = resp.text()?;
+        Ok(RawResponse {
+            status,
+            headers,
+            body,
+        })
+    }
+}
+
+impl PinnacleBlockingApiClient for PinnacleBlockingClient {
+    type Error = PinnacleClientError;
+
+    fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        T: DeserializeOwned + Serialize,
+    {
+        let url = url.into_url()?;
+        self.get_raw(url.clone())?.parse(&url)
+    }
+
+    /// Returns the response as is, whatever its status
+    fn get_raw<U: IntoUrl>(&self, url: U) -> Result<RawResponse, Self::Error> {
+        let url = rebase(self.origin.as_ref(), url.into_url()?);
+        eprintln!("GET {url}");
+        self.send(self.reqwest_client.get(url))
+    }
+
+    fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        B: Serialize,
+        T: DeserializeOwned + Serialize,
+    {
+        let url = rebase(self.origin.as_ref(), url.into_url()?);
+        eprintln!("POST {url}");
+        let req = self.reqwest_client.post(url.clone()).json(body);
+        self.send(req)?.parse(&url)
+    }
+}
+
+/// Synchronous Pinnacle API client caching the responses, stale responses of
+/// [`CachePolicy::StaleWhileRevalidate`] are refreshed in a background thread
+#[derive(Debug)]
+pub struct PinnacleBlockingCachingClient<S = FsCacheStore> {
+    client: PinnacleBlockingClient,
+    cache: ResponseCache<S>,
+}
+
+impl PinnacleBlockingCachingClient {
+    /// Creates a new client caching responses in the `cache_dir` folder for `cache_ttl`
+    pub fn new(
+        username: impl Into<String>,
+        password: impl Into<String>,
+        cache_dir: impl Into<PathBuf>,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self::with_store(username, password, FsCacheStore::new(cache_dir), cache_ttl)
+    }
+}
+
+impl<S: CacheStore + 'static> PinnacleBlockingCachingClient<S> {
+    /// Creates a new client caching responses in the `store`, e.g. in a
+    /// [`MemoryCacheStore`](crate::cache_store::MemoryCacheStore)
+    pub fn with_store(
+        username: impl Into<String>,
+        password: impl Into<String>,
+        store: S,
+        cache_ttl: Duration,
+    ) -> Self {
+        let client = PinnacleBlockingClient::new(username, password);
+        Self::with_client(client, store, cache_ttl)
+    }
+
+    /// Creates a new client caching responses of the `client` in the `store`
+    pub fn with_client(client: PinnacleBlockingClient, store: S, cache_ttl: Duration) -> Self {
+        Self {
+            client,
+            cache: ResponseCache::new(store, cache_ttl),
+        }
+    }
+
+    /// Sets the cache policy for endpoints without their own policy
+    pub fn with_default_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache.set_default_policy(policy);
+        self
+    }
+
+    /// Sets the cache policy for the request type
+    pub fn with_policy<Q: PinnacleApiRequest>(self, policy: CachePolicy) -> Self {
+        self.with_path_policy(Q::PATH, policy)
+    }
+
+    /// Sets the cache policy for the endpoint path, e.g. `/v1/odds`
+    pub fn with_path_policy(mut self, path: impl Into<String>, policy: CachePolicy) -> Self {
+        self.cache.set_policy(path.into(), policy);
+        self
+    }
+
+    /// The cache store
+    pub fn store(&self) -> &S {
+        self.cache.store()
+    }
+
+    /// Typed GET request with the cache behaviour overridden
+    pub fn get_with<Q>(
+        &self,
+        query: &Q,
+        mode: CacheMode,
+    ) -> Result<Q::Response, PinnacleClientError>
+    where
+        Q: PinnacleApiRequest + Serialize,
+    {
+        self.get_by_url_with(request_url(query), mode)
+    }
+
+    /// GET request using full URL with the cache behaviour overridden
+    pub fn get_by_url_with<U, T>(&self, url: U, mode: CacheMode) -> Result<T, PinnacleClientError>
+    where
+        U: IntoUrl,
+        T: DeserializeOwned + Serialize,
+    {
+        let url = url.into_url()?;
+        let policy = self.cache.policy(&url);
+        if mode == CacheMode::Default {
+            let cached = self.cached_entry(&url, policy);
+            if let Some(data) = cached.and_then(|entry| from_json(&entry.body)) {
+                return Ok(data);
+            }
+        }
+        self.fetch(&url, mode, policy)?.parse(&url)
+    }
+
+    /// GET request returning the undecoded response with the cache behaviour overridden
+    pub fn get_raw_with<U: IntoUrl>(
+        &self,
+        url: U,
+        mode: CacheMode,
+    ) -> Result<RawResponse, PinnacleClientError> {
+        let url = url.into_url()?;
+        let policy = self.cache.policy(&url);
+        if mode == CacheMode::Default {
+            if let Some(entry) = self.cached_entry(&url, policy) {
+                return Ok(entry.into());
+            }
+        }
+        self.fetch(&url, mode, policy)
+    }
+
+    /// Requests the API caching the response if it's a valid json
+    fn fetch(
+        &self,
+        url: &Url,
+        mode: CacheMode,
+        policy: CachePolicy,
+    ) -> Result<RawResponse, PinnacleClientError> {
+        let raw = self.client.get_raw(url.clone())?;
+        if mode != CacheMode::Bypass {
+            self.cache.save(url, policy, &raw);
+        }
+        Ok(raw)
+    }
+
+    /// Returns the cached response if it's still usable according to the policy, refreshing a
+    /// stale one in a background thread
+    fn cached_entry(&self, url: &Url, policy: CachePolicy) -> Option<CacheEntry> {
+        let (entry, freshness) = self.cache.lookup(url, policy)?;
+        if freshness == Freshness::Stale {
+            if let Some(revalidation) = self.cache.start_revalidation(url) {
+                let client = self.client.clone();
+                let url = url.clone();
+                std::thread::spawn(move || revalidation.finish(client.get_raw(url)));
+            }
+        }
+        Some(entry)
+    }
+}
+
+impl<S: CacheStore + 'static> PinnacleBlockingApiClient for PinnacleBlockingCachingClient<S> {
+    type Error = PinnacleClientError;
+
+    fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        T: DeserializeOwned + Serialize,
+    {
+        self.get_by_url_with(url, CacheMode::Default)
+    }
+
+    fn get_raw<U: IntoUrl>(&self, url: U) -> Result<RawResponse, Self::Error> {
+        self.get_raw_with(url, CacheMode::Default)
+    }
+
+    /// POST requests are never cached
+    fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl,
+        B: Serialize,
+        T: DeserializeOwned + Serialize,
+    {
+        self.client.post_by_url(url, body)
+    }
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use super::*;
+    use crate::cache_store::MemoryCacheStore;
+    use crate::mock_server::MockServer;
+    use crate::requests::GetSports;
+    use serde_json::json;
+    use std::num::NonZeroUsize;
+
+    #[test]
+    fn test_blocking_caching_client() {
+        // The server runs in its own runtime, the blocking client can't be used within one
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = {
+            let _guard = runtime.enter();
+            MockServer::start().unwrap()
+        };
+        server.set("/v2/sports", json!({"sports": []}));
+
+        let client = PinnacleBlockingClient::new("user", "password").with_origin(server.origin());
+        let store = MemoryCacheStore::new(NonZeroUsize::new(10).unwrap(), Duration::from_secs(60));
+        let client =
+            PinnacleBlockingCachingClient::with_client(client, store, Duration::from_secs(60));
+        assert!(client.get(&GetSports).unwrap().sports.is_empty());
+        assert!(client.get(&GetSports).unwrap().sports.is_empty());
+        assert_eq!(server.requests().len(), 1);
+
+        client.get_with(&GetSports, CacheMode::Bypass).unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_origin_with_path() {
+        use std::io::{Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Responds to a single request with no sports, returning its request line
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = vec![0; 4096];
+            let n = stream.read(&mut request).unwrap();
+            let body = r#"{"sports":[]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+            let request = String::from_utf8_lossy(&request[..n]);
+            request.lines().next().unwrap_or_default().to_string()
+        });
+
+        let origin = Url::parse(&format!("http://{addr}/mock")).unwrap();
+        let client = PinnacleBlockingClient::new("user", "password").with_origin(origin);
+        assert!(client.get(&GetSports).unwrap().sports.is_empty());
+        assert!(server.join().unwrap().starts_with("GET /mock/v2/sports"));
+    }
+}
@@ -1,101 +1,441 @@
-//! Pinnacle API client with the ability to cache requests in the file system.
+//! Pinnacle API client with the ability to cache requests in the file system or in memory.
 //! This replicates the [PinnacleClient] API and is designed for easy client swapping during
 //! development.
-use crate::client::{PinnacleClient, PinnacleClientError};
-use crate::traits::PinnacleApiClient;
+//!
+//! Responses are cached exactly as they were received and decoded on every read, so the cache
+//! keeps the fields the response types don't model and survives changes of the types.
+//!
+//! How long responses are cached is configured per endpoint by [`CachePolicy`]. Incremental
+//! requests, i.e. ones with the `since` parameter, and placed bets are never cached.
+//!
+//! Any other client can be cached with [`PinnacleCachingClient::with_client`], e.g. a
+//! [`PinnacleSingleFlightClient`](crate::single_flight::PinnacleSingleFlightClient) or a
+//! client of a mock server. Its error type has to convert from [`PinnacleClientError`], which
+//! reports decoding of the cached responses.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//! use std::time::Duration;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleCachingClient::new(
+//!     "pinnacle_user",
+//!     "pinnacle_password",
+//!     "cache-folder",
+//!     Duration::from_secs(60),
+//! )
+//! .with_policy::<GetSports>(CachePolicy::Ttl(Duration::from_secs(60 * 60 * 24)))
+//! .with_policy::<GetStraightOdds>(CachePolicy::Ttl(Duration::from_secs(5)));
+//! let sports = client.get(&GetSports).await?;
+//! let fresh_balance = client
+//!     .get_with(&GetClientBalance, CacheMode::ForceRefresh)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::{CacheEntry, CacheStore, FsCacheStore};
+use crate::client::{PinnacleClient, PinnacleClientError, RawResponse};
+use crate::requests::GetBets;
+use crate::traits::{request_url, PinnacleApiClient, PinnacleApiRequest};
 use crate::util::{error_chain, parse_json};
 use async_trait::async_trait;
 use reqwest::{IntoUrl, Url};
-use serde::{de::DeserializeOwned, Serialize};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
-use std::fs;
+use std::future::Future;
 use std::marker::Send;
-use std::path::{Path, PathBuf};
-use std::time::{Duration, SystemTime};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Pinnacle API client
 #[derive(Debug)]
-pub struct PinnacleCachingClient {
-    client: PinnacleClient,
-    cache_dir: PathBuf,
-    cache_ttl: Duration,
+pub struct PinnacleCachingClient<S = FsCacheStore, C = PinnacleClient> {
+    client: Arc<C>,
+    cache: ResponseCache<S>,
+}
+
+/// The store with the cache policies, shared by the caching clients and the `CacheLayer` of
+/// the `tower` middleware
+#[derive(Debug)]
+pub(crate) struct ResponseCache<S> {
+    store: Arc<S>,
+    default_policy: CachePolicy,
+    policies: HashMap<String, CachePolicy>,
+    revalidating: Arc<Mutex<HashSet<Url>>>,
+}
+
+/// A response being revalidated, the url is released when it's dropped
+pub(crate) struct Revalidation<S> {
+    url: Url,
+    store: Arc<S>,
+    revalidating: Arc<Mutex<HashSet<Url>>>,
+}
+
+/// How long responses are cached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// Cached responses are used for the duration
+    Ttl(Duration),
+    /// Responses aren't cached
+    Never,
+    /// Cached responses never expire
+    Forever,
+    /// Cached responses are used for `ttl`, then for another `stale` period they're still
+    /// returned, but refreshed in background. Outside of a Tokio runtime stale responses are
+    /// refreshed before returning instead.
+    StaleWhileRevalidate {
+        /// How long the response is fresh
+        ttl: Duration,
+        /// How long the response can be used after it isn't fresh anymore
+        stale: Duration,
+    },
+}
+
+/// Per-call cache behaviour
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CacheMode {
+    /// Follow the endpoint policy
+    #[default]
+    Default,
+    /// Neither read nor write the cache
+    Bypass,
+    /// Ignore the cached response, but cache the new one
+    ForceRefresh,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Freshness {
+    Fresh,
+    Stale,
+}
+
+impl CachePolicy {
+    pub(crate) fn freshness(&self, age: Duration) -> Option<Freshness> {
+        match *self {
+            Self::Ttl(ttl) => (age < ttl).then_some(Freshness::Fresh),
+            Self::Never => None,
+            Self::Forever => Some(Freshness::Fresh),
+            Self::StaleWhileRevalidate { ttl, stale } => {
+                if age < ttl {
+                    Some(Freshness::Fresh)
+                } else if age < ttl + stale {
+                    Some(Freshness::Stale)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl<S> ResponseCache<S> {
+    /// Creates a cache in the `store` with the default policy of `cache_ttl`
+    pub(crate) fn new(store: S, cache_ttl: Duration) -> Self {
+        Self {
+            store: Arc::new(store),
+            default_policy: CachePolicy::Ttl(cache_ttl),
+            policies: HashMap::new(),
+            revalidating: Default::default(),
+        }
+    }
+
+    /// Sets the cache policy for endpoints without their own policy
+    pub(crate) fn set_default_policy(&mut self, policy: CachePolicy) {
+        self.default_policy = policy;
+    }
+
+    /// Sets the cache policy for the endpoint path
+    pub(crate) fn set_policy(&mut self, path: String, policy: CachePolicy) {
+        self.policies.insert(path, policy);
+    }
+
+    /// The cache store
+    pub(crate) fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Cache policy of the url, incremental requests and bets are never cached
+    pub(crate) fn policy(&self, url: &Url) -> CachePolicy {
+        if url.path() == GetBets::PATH || url.query_pairs().any(|(k, _)| k == "since") {
+            return CachePolicy::Never;
+        }
+        self.policies
+            .get(url.path())
+            .copied()
+            .unwrap_or(self.default_policy)
+    }
+}
+
+impl<S: CacheStore + 'static> ResponseCache<S> {
+    /// Returns the cached response if it's still usable according to the policy
+    pub(crate) fn lookup(&self, url: &Url, policy: CachePolicy) -> Option<(CacheEntry, Freshness)> {
+        let found = self.store.load(url).and_then(|entry| {
+            let freshness = policy.freshness(entry.age())?;
+            Some((entry, freshness))
+        });
+        #[cfg(feature = "metrics")]
+        {
+            let result = match found {
+                Some((_, Freshness::Fresh)) => "hit",
+                Some((_, Freshness::Stale)) => "stale",
+                None => "miss",
+            };
+            crate::client_metrics::record_cache_lookup(url, result);
+        }
+        found
+    }
+
+    /// Caches a fetched response if it's a successful valid json and the policy allows
+    pub(crate) fn save(&self, url: &Url, policy: CachePolicy, raw: &RawResponse) {
+        if policy != CachePolicy::Never && raw.is_cacheable() {
+            self.store.save(url, CacheEntry::from_response(raw));
+        }
+    }
+
+    /// Marks the url as being revalidated, `None` if it already is
+    pub(crate) fn start_revalidation(&self, url: &Url) -> Option<Revalidation<S>> {
+        if !lock(&self.revalidating).insert(url.clone()) {
+            return None;
+        }
+        Some(Revalidation {
+            url: url.clone(),
+            store: self.store.clone(),
+            revalidating: self.revalidating.clone(),
+        })
+    }
+
+    /// Refreshes the cached response with the `fetch` future in background, returns `false`
+    /// outside of a Tokio runtime
+    pub(crate) fn revalidate<F, E>(&self, url: &Url, fetch: F) -> bool
+    where
+        F: Future<Output = Result<RawResponse, E>> + Send + 'static,
+        E: Error + Send + 'static,
+    {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return false;
+        };
+        if let Some(revalidation) = self.start_revalidation(url) {
+            runtime.spawn(async move { revalidation.finish(fetch.await) });
+        }
+        true
+    }
+}
+
+impl<S> Clone for ResponseCache<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            default_policy: self.default_policy,
+            policies: self.policies.clone(),
+            revalidating: self.revalidating.clone(),
+        }
+    }
+}
+
+impl<S: CacheStore> Revalidation<S> {
+    /// Caches the refreshed response if it's a valid json
+    pub(crate) fn finish<E: Error + 'static>(self, result: Result<RawResponse, E>) {
+        let url = &self.url;
+        let raw = result.map_err(|e| error_chain(&e)).and_then(|raw| {
+            raw.parse::<IgnoredAny>(url).map_err(|e| error_chain(&e))?;
+            Ok(raw)
+        });
+        match raw {
+            Ok(raw) => self.store.save(url, CacheEntry::from_response(&raw)),
+            Err(e) => eprintln!("Can't revalidate {url} <-- {e}"),
+        }
+    }
+}
+
+impl<S> Drop for Revalidation<S> {
+    fn drop(&mut self) {
+        lock(&self.revalidating).remove(&self.url);
+    }
 }
 
 impl PinnacleCachingClient {
-    /// Creates a new client
+    /// Creates a new client caching responses in the `cache_dir` folder for `cache_ttl`
     pub fn new(
         username: impl Into<String>,
         password: impl Into<String>,
         cache_dir: impl Into<PathBuf>,
         cache_ttl: Duration,
     ) -> Self {
-        let client = PinnacleClient::new(username, password);
-        let cache_dir = cache_dir.into();
-        create_cache_dir(cache_dir.as_path());
+        Self::with_store(username, password, FsCacheStore::new(cache_dir), cache_ttl)
+    }
+}
+
+impl<S: CacheStore + 'static> PinnacleCachingClient<S> {
+    /// Creates a new client caching responses in the `store`, e.g. in a
+    /// [`MemoryCacheStore`](crate::cache_store::MemoryCacheStore)
+    pub fn with_store(
+        username: impl Into<String>,
+        password: impl Into<String>,
+        store: S,
+        cache_ttl: Duration,
+    ) -> Self {
+        Self::with_client(PinnacleClient::new(username, password), store, cache_ttl)
+    }
+}
+
+impl<S, C> PinnacleCachingClient<S, C>
+where
+    S: CacheStore + 'static,
+    C: PinnacleApiClient + Send + Sync + 'static,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    /// Creates a new client caching responses of the `client` in the `store`
+    pub fn with_client(client: C, store: S, cache_ttl: Duration) -> Self {
         Self {
-            client,
-            cache_dir,
-            cache_ttl,
+            client: Arc::new(client),
+            cache: ResponseCache::new(store, cache_ttl),
+        }
+    }
+
+    /// Sets the cache policy for endpoints without their own policy
+    pub fn with_default_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache.set_default_policy(policy);
+        self
+    }
+
+    /// Sets the cache policy for the request type
+    pub fn with_policy<Q: PinnacleApiRequest>(self, policy: CachePolicy) -> Self {
+        self.with_path_policy(Q::PATH, policy)
+    }
+
+    /// Sets the cache policy for the endpoint path, e.g. `/v1/odds`
+    pub fn with_path_policy(mut self, path: impl Into<String>, policy: CachePolicy) -> Self {
+        self.cache.set_policy(path.into(), policy);
+        self
+    }
+
+    /// The cache store
+    pub fn store(&self) -> &S {
+        self.cache.store()
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    /// Typed GET request with the cache behaviour overridden
+    pub async fn get_with<Q>(&self, query: &Q, mode: CacheMode) -> Result<Q::Response, C::Error>
+    where
+        Q: PinnacleApiRequest + Serialize,
+    {
+        self.get_by_url_with(request_url(query), mode).await
+    }
+
+    /// GET request using full URL with the cache behaviour overridden
+    pub async fn get_by_url_with<U, T>(&self, url: U, mode: CacheMode) -> Result<T, C::Error>
+    where
+        U: IntoUrl + Send,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url().map_err(PinnacleClientError::from)?;
+        let policy = self.cache.policy(&url);
+        if mode == CacheMode::Default {
+            let cached = self.cached_entry(&url, policy);
+            if let Some(data) = cached.and_then(|entry| from_json(&entry.body)) {
+                return Ok(data);
+            }
         }
+        Ok(self.fetch(&url, mode, policy).await?.parse(&url)?)
     }
 
-    fn get_cached<T>(&self, url: &Url) -> Option<T>
+    /// GET request returning the undecoded response with the cache behaviour overridden
+    pub async fn get_raw_with<U>(&self, url: U, mode: CacheMode) -> Result<RawResponse, C::Error>
     where
-        T: DeserializeOwned + Serialize,
+        U: IntoUrl + Send,
     {
-        let filename = self.filename_by_url(url);
-        file_modified_ago_if_exists(filename.as_path())
-            .filter(|modified| modified < &self.cache_ttl)
-            .and_then(|_| read_file(filename.as_path()))
-            .and_then(|s| from_json(&s))
+        let url = url.into_url().map_err(PinnacleClientError::from)?;
+        let policy = self.cache.policy(&url);
+        if mode == CacheMode::Default {
+            if let Some(entry) = self.cached_entry(&url, policy) {
+                return Ok(entry.into());
+            }
+        }
+        self.fetch(&url, mode, policy).await
     }
 
-    fn to_cache<T: Serialize>(&self, url: &Url, data: &T) {
-        let Some(content) = to_json(data) else { return };
-        let filename = self.filename_by_url(url);
-        write_file(filename.as_path(), &content);
+    /// Requests the API caching the response if it's a valid json
+    async fn fetch(
+        &self,
+        url: &Url,
+        mode: CacheMode,
+        policy: CachePolicy,
+    ) -> Result<RawResponse, C::Error> {
+        let raw = self.client.get_raw(url.clone()).await?;
+        if mode != CacheMode::Bypass {
+            self.cache.save(url, policy, &raw);
+        }
+        Ok(raw)
     }
 
-    fn filename_by_url(&self, url: &Url) -> PathBuf {
-        self.cache_dir
-            .as_path()
-            .join(url_to_filename(url))
-            .with_extension("json")
+    /// Returns the cached response if it's still usable according to the policy
+    fn cached_entry(&self, url: &Url, policy: CachePolicy) -> Option<CacheEntry> {
+        let (entry, freshness) = self.cache.lookup(url, policy)?;
+        if freshness == Freshness::Stale {
+            let client = self.client.clone();
+            let fetch = {
+                let url = url.clone();
+                async move { client.get_raw(url).await }
+            };
+            // Without a runtime to refresh it in background the stale response is refreshed
+            // inline
+            if !self.cache.revalidate(url, fetch) {
+                return None;
+            }
+        }
+        Some(entry)
     }
 }
 
 #[async_trait]
-impl PinnacleApiClient for PinnacleCachingClient {
-    type Error = PinnacleClientError;
+impl<S, C> PinnacleApiClient for PinnacleCachingClient<S, C>
+where
+    S: CacheStore + 'static,
+    C: PinnacleApiClient + Send + Sync + 'static,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    type Error = C::Error;
 
     async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
     where
         U: IntoUrl + Send,
         T: DeserializeOwned + Serialize + Send,
     {
-        let url = url.into_url()?;
-        let data = if let Some(data) = self.get_cached(&url) {
-            data
-        } else {
-            let data = self.client.get_by_url(url.clone()).await?;
-            self.to_cache(&url, &data);
-            data
-        };
-        Ok(data)
+        self.get_by_url_with(url, CacheMode::Default).await
     }
-}
 
-fn to_json(data: &impl Serialize) -> Option<String> {
-    match serde_json::to_string(data) {
-        Ok(s) => Some(s),
-        Err(e) => {
-            eprintln!("Can't serialize data <-- {}", error_chain(&e));
-            None
-        }
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        self.get_raw_with(url, CacheMode::Default).await
+    }
+
+    /// POST requests are never cached
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        self.client.post_by_url(url, body).await
     }
 }
 
-fn from_json<T: DeserializeOwned>(s: &str) -> Option<T> {
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) fn from_json<T: DeserializeOwned>(s: &str) -> Option<T> {
     match parse_json(s) {
         Ok(data) => Some(data),
         Err(e) => {
@@ -105,83 +445,126 @@ fn from_json<T: DeserializeOwned>(s: &str) -> Option<T> {
     }
 }
 
-fn create_cache_dir(path: &Path) {
-    if let Err(e) = fs::create_dir_all(path) {
-        eprintln!("Can't create cache folder {path:?} <-- {}", error_chain(&e));
-    }
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache_store::MemoryCacheStore;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn write_file(path: &Path, content: &str) {
-    if let Err(e) = fs::write(path, content) {
-        eprintln!("Can't write into file {path:?} <-- {}", error_chain(&e));
+    #[test]
+    fn test_cache_policy_freshness() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            CachePolicy::Ttl(secs(5)).freshness(secs(1)),
+            Some(Freshness::Fresh)
+        );
+        assert_eq!(CachePolicy::Ttl(secs(5)).freshness(secs(5)), None);
+        assert_eq!(CachePolicy::Never.freshness(secs(0)), None);
+        assert_eq!(
+            CachePolicy::Forever.freshness(secs(1_000_000)),
+            Some(Freshness::Fresh)
+        );
+        let swr = CachePolicy::StaleWhileRevalidate {
+            ttl: secs(5),
+            stale: secs(10),
+        };
+        assert_eq!(swr.freshness(secs(1)), Some(Freshness::Fresh));
+        assert_eq!(swr.freshness(secs(10)), Some(Freshness::Stale));
+        assert_eq!(swr.freshness(secs(15)), None);
     }
-}
 
-fn read_file(path: &Path) -> Option<String> {
-    match fs::read_to_string(path) {
-        Ok(s) => Some(s),
-        Err(e) => {
-            eprintln!("Can't read file {path:?} <-- {}", error_chain(&e));
-            None
-        }
-    }
-}
+    #[test]
+    fn test_cache_policy_by_url() {
+        use crate::requests::{GetBets, GetSports, GetStraightOdds};
 
-fn url_to_filename(url: &Url) -> String {
-    let mut filename = String::new();
+        let secs = Duration::from_secs;
+        let store = MemoryCacheStore::new(NonZeroUsize::new(1).unwrap(), secs(1));
+        let client = PinnacleCachingClient::with_store("", "", store, secs(60))
+            .with_policy::<GetSports>(CachePolicy::Forever)
+            .with_path_policy("/v1/odds", CachePolicy::Ttl(secs(1)));
+        let policy = |req: &str| client.cache.policy(&Url::parse(req).unwrap());
 
-    if let Some(path_segments) = url.path_segments() {
-        for (i, segment) in path_segments.enumerate() {
-            if i > 0 {
-                filename.push('_');
-            }
-            filename.push_str(segment);
-        }
+        let sports = request_url(&GetSports);
+        assert_eq!(policy(&sports), CachePolicy::Forever);
+        let odds = request_url(&GetStraightOdds::default());
+        assert_eq!(policy(&odds), CachePolicy::Ttl(secs(1)));
+        let odds_since = request_url(&GetStraightOdds {
+            since: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(policy(&odds_since), CachePolicy::Never);
+        let bets = request_url(&GetBets::default());
+        assert_eq!(policy(&bets), CachePolicy::Never);
+        let balance = "https://api.pinnacle.com/v1/client/balance";
+        assert_eq!(policy(balance), CachePolicy::Ttl(secs(60)));
     }
 
-    if let Some(query) = url.query() {
-        filename.push('_');
-        filename.push_str(&query.replace('?', "").replace('&', "_").replace('=', "-"));
+    #[derive(Debug, displaydoc::Display, thiserror::Error)]
+    enum InnerError {
+        /// client
+        Client(#[from] PinnacleClientError),
     }
 
-    filename
-}
+    /// Counts the requests, responding with no sports
+    #[derive(Default)]
+    struct CountingClient(AtomicUsize);
 
-/// Returns the time elapsed since the file modification, or None for a non-existent file.
-fn file_modified_ago_if_exists(path: &Path) -> Option<Duration> {
-    if !path.is_file() {
-        return None;
+    #[async_trait]
+    impl PinnacleApiClient for CountingClient {
+        type Error = InnerError;
+
+        async fn get_by_url<U, T>(&self, _url: U) -> Result<T, Self::Error>
+        where
+            U: IntoUrl + Send,
+            T: DeserializeOwned + Serialize + Send,
+        {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(serde_json::from_str(r#"{"sports":[]}"#).unwrap())
+        }
     }
-    match file_modified_ago(path) {
-        Ok(d) => Some(d),
-        Err(e) => {
-            eprintln!(
-                "Can't get file modification time {path:?} <-- {}",
-                error_chain(&*e)
-            );
-            None
+
+    #[tokio::test]
+    async fn test_caching_inner_client() {
+        let secs = Duration::from_secs;
+        let store = MemoryCacheStore::new(NonZeroUsize::new(1).unwrap(), secs(60));
+        let client = PinnacleCachingClient::with_client(CountingClient::default(), store, secs(60));
+        let url = "https://api.pinnacle.com/v3/sports";
+        for _ in 0..2 {
+            let sports: serde_json::Value = client.get_by_url(url).await.unwrap();
+            assert_eq!(sports["sports"], serde_json::json!([]));
         }
+        assert_eq!(client.inner().0.load(Ordering::SeqCst), 1);
     }
-}
 
-fn file_modified_ago(path: &Path) -> Result<Duration, Box<dyn Error>> {
-    let modified = fs::metadata(path)?.modified()?;
-    let now_since_epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH)?;
-    let modified_since_epoch = modified.duration_since(SystemTime::UNIX_EPOCH)?;
-    now_since_epoch
-        .checked_sub(modified_since_epoch)
-        .ok_or("times mismatch".into())
-}
+    #[test]
+    fn test_revalidate_without_runtime() {
+        use std::future::Future;
+        use std::task::{Context, Poll, Wake, Waker};
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        struct NoopWake;
 
-    #[test]
-    fn test_url_to_filename() {
-        let url =
-            Url::parse("https://example.com/path/to/file?param1=value1&param2=value2").unwrap();
-        let filename = url_to_filename(&url);
-        assert_eq!(filename, "path_to_file_param1-value1_param2-value2");
+        impl Wake for NoopWake {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let secs = Duration::from_secs;
+        let store = MemoryCacheStore::new(NonZeroUsize::new(1).unwrap(), secs(60));
+        let client = PinnacleCachingClient::with_client(CountingClient::default(), store, secs(60))
+            .with_default_policy(CachePolicy::StaleWhileRevalidate {
+                ttl: secs(0),
+                stale: secs(60),
+            });
+        let waker = Waker::from(Arc::new(NoopWake));
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..2 {
+            let get = client.get_raw("https://api.pinnacle.com/v3/sports");
+            let Poll::Ready(raw) = std::pin::pin!(get).poll(&mut cx) else {
+                panic!("the counting client never waits");
+            };
+            assert_eq!(raw.unwrap().status, 200);
+        }
+        // The stale response is refreshed inline instead of panicking on `tokio::spawn`
+        assert_eq!(client.inner().0.load(Ordering::SeqCst), 2);
     }
 }
@@ -2,9 +2,11 @@
 use crate::{traits::PinnacleApiClient, util::parse_json};
 use async_trait::async_trait;
 use displaydoc::Display;
-use reqwest::IntoUrl;
-use serde::{de::DeserializeOwned, Serialize};
+use reqwest::{IntoUrl, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::marker::Send;
+use std::path::PathBuf;
 use thiserror::Error;
 
 /// Pinnacle API client
@@ -12,6 +14,7 @@ use thiserror::Error;
 pub struct PinnacleClient {
     username: String,
     password: String,
+    origin: Option<Url>,
     reqwest_client: reqwest::Client,
 }
 
@@ -20,6 +23,8 @@ pub struct PinnacleClient {
 pub enum PinnacleClientError {
     /// reqwest
     Reqwest(#[from] reqwest::Error),
+    /// http status {0} from {1}
+    HttpStatus(u16, reqwest::Url, String),
     /// empty json from {0}
     EmptyJson(reqwest::Url),
     /// decode json from {1}
@@ -27,6 +32,133 @@ pub enum PinnacleClientError {
         #[source] serde_path_to_error::Error<serde_json::Error>,
         reqwest::Url,
     ),
+    /// no cassette recorded for {0}
+    CassetteNotFound(reqwest::Url),
+    /// cassette {1:?}
+    Cassette(#[source] std::io::Error, PathBuf),
+    /// coalesced request to {1} failed: {0}
+    Coalesced(String, reqwest::Url),
+    /// timeout of request to {0}
+    Timeout(reqwest::Url),
+    /// encode json for {1}
+    EncodeJson(#[source] serde_json::Error, reqwest::Url),
+    /// the client doesn't support POST requests, sent to {0}
+    PostNotSupported(reqwest::Url),
+}
+
+/// Response as it was received, before any decoding
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RawResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// Response headers
+    pub headers: BTreeMap<String, String>,
+    /// Response body
+    pub body: String,
+}
+
+impl PinnacleClientError {
+    /// Whether the request timed out
+    pub fn is_timeout(&self) -> bool {
+        match self {
+            Self::Reqwest(e) => e.is_timeout(),
+            Self::Timeout(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the request definitely had no effect: it wasn't sent, or the API rejected it
+    /// with a 4xx status
+    pub fn is_definitive_failure(&self) -> bool {
+        match self {
+            Self::Reqwest(e) => e.is_builder() || e.is_connect(),
+            Self::HttpStatus(status, _, _) => (400..500).contains(status),
+            Self::EncodeJson(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the server responded with a 5xx status
+    pub fn is_server_error(&self) -> bool {
+        matches!(self, Self::HttpStatus(status, _, _) if (500..600).contains(status))
+    }
+}
+
+impl RawResponse {
+    /// Decodes the body of a successful response, the `url` is used for error reporting
+    pub fn parse<T: DeserializeOwned>(&self, url: &Url) -> Result<T, PinnacleClientError> {
+        if !(200..300).contains(&self.status) {
+            return Err(PinnacleClientError::HttpStatus(
+                self.status,
+                url.clone(),
+                self.body.clone(),
+            ));
+        }
+        if self.body.is_empty() {
+            #[cfg(feature = "metrics")]
+            crate::client_metrics::record_decode_error(url, "empty_json");
+            return Err(PinnacleClientError::EmptyJson(url.clone()));
+        }
+        #[cfg(feature = "extra-fields")]
+        let (decoded, extra) = crate::util::collect_extra_fields(|| parse_json(&self.body));
+        #[cfg(feature = "extra-fields")]
+        warn_extra_fields(url, extra);
+        #[cfg(not(feature = "extra-fields"))]
+        let decoded = parse_json(&self.body);
+        decoded.map_err(|e| {
+            #[cfg(feature = "metrics")]
+            crate::client_metrics::record_decode_error(url, "decode_json");
+            PinnacleClientError::DecodeJson(e, url.clone())
+        })
+    }
+
+    /// Whether the response is a successful valid json worth caching
+    pub(crate) fn is_cacheable(&self) -> bool {
+        (200..300).contains(&self.status)
+            && serde_json::from_str::<serde::de::IgnoredAny>(&self.body).is_ok()
+    }
+}
+
+/// Replaces scheme, host and port of the url with the origin, if any, prefixing the path with
+/// the path of the origin, e.g. `http://localhost/mock` sends `/v1/odds` to `/mock/v1/odds`
+pub(crate) fn rebase(origin: Option<&Url>, mut url: Url) -> Url {
+    if let Some(origin) = origin {
+        url.set_scheme(origin.scheme()).ok();
+        url.set_host(origin.host_str()).ok();
+        url.set_port(origin.port()).ok();
+        let path = format!("{}{}", origin.path().trim_end_matches('/'), url.path());
+        url.set_path(&path);
+    }
+    url
+}
+
+/// Warns about the unmodeled fields of an endpoint, once per field, and counts them in every
+/// response
+#[cfg(feature = "extra-fields")]
+fn warn_extra_fields(url: &Url, names: std::collections::BTreeSet<String>) {
+    use std::collections::BTreeSet;
+    use std::sync::Mutex;
+
+    static WARNED: Mutex<BTreeSet<(String, String)>> = Mutex::new(BTreeSet::new());
+
+    #[cfg(feature = "metrics")]
+    for name in names.iter() {
+        crate::client_metrics::record_extra_field(url, name);
+    }
+    let Ok(mut warned) = WARNED.lock() else {
+        return;
+    };
+    let new: Vec<_> = names
+        .into_iter()
+        .filter(|name| warned.insert((url.path().to_string(), name.clone())))
+        .collect();
+    if !new.is_empty() {
+        eprintln!(
+            "Unmodeled fields in the response of {}: {}",
+            url.path(),
+            new.join(", ")
+        );
+    }
 }
 
 impl PinnacleClient {
@@ -38,9 +170,74 @@ impl PinnacleClient {
         Self {
             username,
             password,
+            origin: None,
             reqwest_client,
         }
     }
+
+    /// Sends the requests to the `origin` instead of the API, e.g. to a local mock server
+    pub fn with_origin(mut self, origin: Url) -> Self {
+        self.origin = Some(origin);
+        self
+    }
+
+    fn rebase(&self, url: Url) -> Url {
+        rebase(self.origin.as_ref(), url)
+    }
+
+    /// POST request with an already encoded json body, returning the response as is. Like
+    /// [`PinnacleApiClient::get_raw`] it's the only place the url is rebased onto the origin.
+    pub(crate) async fn post_raw(
+        &self,
+        url: Url,
+        body: String,
+    ) -> Result<RawResponse, PinnacleClientError> {
+        let url = self.rebase(url);
+        eprintln!("POST {url}");
+        let req = self
+            .reqwest_client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body);
+        self.send(req).await
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<RawResponse, PinnacleClientError> {
+        let req = req
+            .basic_auth(&self.username, Some(&self.password))
+            .build()?;
+        #[cfg(feature = "metrics")]
+        let (method, url, started) = (
+            req.method().clone(),
+            req.url().clone(),
+            std::time::Instant::now(),
+        );
+        let result = self.execute(req).await;
+        #[cfg(feature = "metrics")]
+        crate::client_metrics::record_request(
+            method.as_str(),
+            &url,
+            result.as_ref().ok().map(|raw| raw.status),
+            started.elapsed(),
+        );
+        result
+    }
+
+    async fn execute(&self, req: reqwest::Request) -> Result<RawResponse, PinnacleClientError> {
+        let resp = self.reqwest_client.execute(req).await?;
+        let status = resp.status().as_u16();
+        let headers = resp
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.to_string(), String::from_utf8_lossy(v.as_bytes()).into()))
+            .collect();
+        let body = resp.text().await?;
+        Ok(RawResponse {
+            status,
+            headers,
+            body,
+        })
+    }
 }
 
 #[async_trait]
@@ -53,19 +250,78 @@ impl PinnacleApiClient for PinnacleClient {
         T: DeserializeOwned + Serialize + Send,
     {
         let url = url.into_url()?;
-        println!("GET {url}");
-        let text = self
-            .reqwest_client
-            .get(url.clone())
-            .basic_auth(&self.username, Some(&self.password))
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
-        if text.is_empty() {
-            return Err(PinnacleClientError::EmptyJson(url));
-        }
-        parse_json(&text).map_err(|e| PinnacleClientError::DecodeJson(e, url))
+        self.get_raw(url.clone()).await?.parse(&url)
+    }
+
+    /// Returns the response as is, whatever its status
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        let url = self.rebase(url.into_url()?);
+        eprintln!("GET {url}");
+        self.send(self.reqwest_client.get(url)).await
+    }
+
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        let body = serde_json::to_string(body)
+            .map_err(|e| PinnacleClientError::EncodeJson(e, url.clone()))?;
+        self.post_raw(url.clone(), body).await?.parse(&url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebase() {
+        let url = Url::parse("https://api.pinnacle.com/v1/odds?sportId=29").unwrap();
+        assert_eq!(rebase(None, url.clone()), url);
+
+        let origin = Url::parse("http://127.0.0.1:8080").unwrap();
+        assert_eq!(
+            rebase(Some(&origin), url.clone()).as_str(),
+            "http://127.0.0.1:8080/v1/odds?sportId=29"
+        );
+        let origin = Url::parse("http://localhost/mock/").unwrap();
+        assert_eq!(
+            rebase(Some(&origin), url).as_str(),
+            "http://localhost/mock/v1/odds?sportId=29"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_origin_with_path() {
+        use crate::requests::GetSports;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Responds to a single request with no sports, returning its request line
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let n = stream.read(&mut request).await.unwrap();
+            let body = r#"{"sports":[]}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..n]);
+            request.lines().next().unwrap_or_default().to_string()
+        });
+
+        let origin = Url::parse(&format!("http://{addr}/mock")).unwrap();
+        let client = PinnacleClient::new("user", "password").with_origin(origin);
+        assert!(client.get(&GetSports).await.unwrap().sports.is_empty());
+        assert!(server.await.unwrap().starts_with("GET /mock/v2/sports"));
     }
 }
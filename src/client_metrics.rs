@@ -0,0 +1,268 @@
+//! Metrics of the API calls recorded with the [`metrics`] facade, requires the `metrics` feature.
+//!
+//! Nothing is exported until a recorder is installed, e.g. `metrics-exporter-prometheus`.
+//! All the metrics are labelled with the `endpoint` path, e.g. `/v1/odds`.
+//!
+//! Requests, decoding errors, cache lookups and, with the `extra-fields` feature, unmodeled
+//! response fields are recorded by all the clients. Retries,
+//! rate limit waits and the requests served by a service stack are only recorded by the layers
+//! of the `tower` [`middleware`](crate::middleware), as the other clients neither retry nor
+//! rate limit.
+//!
+//! ```rust
+//! pinnacle::client_metrics::describe_metrics();
+//! ```
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+use reqwest::Url;
+use std::time::Duration;
+
+/// Requests sent, labelled with `method` and `status`, which is `error` if there was no response
+pub const REQUESTS_METRIC: &str = "pinnacle_requests_total";
+/// Duration of the requests, labelled with `method`
+pub const REQUEST_DURATION_METRIC: &str = "pinnacle_request_duration_seconds";
+/// Responses which couldn't be decoded, labelled with `error`: `decode_json` or `empty_json`
+pub const DECODE_ERRORS_METRIC: &str = "pinnacle_decode_errors_total";
+/// Requests retried by the `tower` middleware
+pub const RETRIES_METRIC: &str = "pinnacle_retries_total";
+/// Time requests waited for the rate limit of the `tower` middleware
+pub const RATE_LIMIT_WAIT_METRIC: &str = "pinnacle_rate_limit_wait_seconds";
+/// Cache lookups, labelled with `result`: `hit`, `stale` or `miss`
+pub const CACHE_LOOKUPS_METRIC: &str = "pinnacle_cache_lookups_total";
+/// Fields of the decoded responses the structs don't model, labelled with `field`, only
+/// recorded with the `extra-fields` feature
+pub const EXTRA_FIELDS_METRIC: &str = "pinnacle_extra_fields_total";
+/// Requests served by the `tower` stack at the `MetricsLayer`, labelled like
+/// [`REQUESTS_METRIC`]
+pub const SERVICE_REQUESTS_METRIC: &str = "pinnacle_service_requests_total";
+/// Duration of the requests served by the `tower` stack at the `MetricsLayer`
+pub const SERVICE_REQUEST_DURATION_METRIC: &str = "pinnacle_service_request_duration_seconds";
+
+/// Registers the descriptions of the metrics with the installed recorder
+pub fn describe_metrics() {
+    describe_counter!(REQUESTS_METRIC, Unit::Count, "Requests sent to the API");
+    describe_histogram!(
+        REQUEST_DURATION_METRIC,
+        Unit::Seconds,
+        "Duration of the API requests"
+    );
+    describe_counter!(
+        DECODE_ERRORS_METRIC,
+        Unit::Count,
+        "API responses which couldn't be decoded"
+    );
+    describe_counter!(RETRIES_METRIC, Unit::Count, "Retried API requests");
+    describe_histogram!(
+        RATE_LIMIT_WAIT_METRIC,
+        Unit::Seconds,
+        "Time API requests waited for the rate limit"
+    );
+    describe_counter!(
+        CACHE_LOOKUPS_METRIC,
+        Unit::Count,
+        "Lookups of cached API responses"
+    );
+    describe_counter!(
+        EXTRA_FIELDS_METRIC,
+        Unit::Count,
+        "Unmodeled fields of the API responses"
+    );
+    describe_counter!(
+        SERVICE_REQUESTS_METRIC,
+        Unit::Count,
+        "Requests served by the service stack"
+    );
+    describe_histogram!(
+        SERVICE_REQUEST_DURATION_METRIC,
+        Unit::Seconds,
+        "Duration of the requests served by the service stack"
+    );
+}
+
+/// Records a sent request, `status` is `None` if there was no response
+pub(crate) fn record_request(method: &str, url: &Url, status: Option<u16>, elapsed: Duration) {
+    record_requests(
+        (REQUESTS_METRIC, REQUEST_DURATION_METRIC),
+        method,
+        url,
+        status,
+        elapsed,
+    );
+}
+
+/// Records a request served by the service stack
+#[cfg(feature = "tower")]
+pub(crate) fn record_service_request(
+    method: &str,
+    url: &Url,
+    status: Option<u16>,
+    elapsed: Duration,
+) {
+    record_requests(
+        (SERVICE_REQUESTS_METRIC, SERVICE_REQUEST_DURATION_METRIC),
+        method,
+        url,
+        status,
+        elapsed,
+    );
+}
+
+/// Increments the counter and records the duration into the histogram
+fn record_requests(
+    (requests, duration): (&'static str, &'static str),
+    method: &str,
+    url: &Url,
+    status: Option<u16>,
+    elapsed: Duration,
+) {
+    let endpoint = url.path().to_string();
+    let status = status.map_or_else(|| "error".into(), |s| s.to_string());
+    let method = method.to_string();
+    let labels = [
+        ("endpoint", endpoint.clone()),
+        ("method", method.clone()),
+        ("status", status),
+    ];
+    counter!(requests, &labels).increment(1);
+    histogram!(duration, "endpoint" => endpoint, "method" => method).record(elapsed.as_secs_f64());
+}
+
+/// Records a response which couldn't be decoded
+pub(crate) fn record_decode_error(url: &Url, error: &'static str) {
+    counter!(DECODE_ERRORS_METRIC, "endpoint" => url.path().to_string(), "error" => error)
+        .increment(1);
+}
+
+/// Records a retried request
+#[cfg(feature = "tower")]
+pub(crate) fn record_retry(url: &Url) {
+    counter!(RETRIES_METRIC, "endpoint" => url.path().to_string()).increment(1);
+}
+
+/// Records the time a request waited for the rate limit
+#[cfg(feature = "tower")]
+pub(crate) fn record_rate_limit_wait(url: &Url, wait: Duration) {
+    histogram!(RATE_LIMIT_WAIT_METRIC, "endpoint" => url.path().to_string())
+        .record(wait.as_secs_f64());
+}
+
+/// Records a cache lookup
+pub(crate) fn record_cache_lookup(url: &Url, result: &'static str) {
+    counter!(CACHE_LOOKUPS_METRIC, "endpoint" => url.path().to_string(), "result" => result)
+        .increment(1);
+}
+
+/// Records a field of a response the struct doesn't model
+#[cfg(feature = "extra-fields")]
+pub(crate) fn record_extra_field(url: &Url, field: &str) {
+    counter!(EXTRA_FIELDS_METRIC, "endpoint" => url.path().to_string(), "field" => field.to_string())
+        .increment(1);
+}
+
+#[cfg(all(test, feature = "mock-server"))]
+mod tests {
+    use super::*;
+    use crate::cache_store::MemoryCacheStore;
+    use crate::caching_client::{CachePolicy, PinnacleCachingClient};
+    use crate::client::PinnacleClientError;
+    use crate::mock_server::MockServer;
+    use crate::requests::{GetClientBalance, GetLeagues, GetPeriods, GetSports};
+    use crate::traits::{PinnacleApiClient, PinnacleApiRequest};
+    use metrics::SharedString;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use metrics_util::CompositeKey;
+    use serde_json::json;
+    use std::num::NonZeroUsize;
+
+    /// A recorded metric
+    type Recorded = (CompositeKey, Option<Unit>, Option<SharedString>, DebugValue);
+
+    /// Sum of the counters of the name with the labels
+    fn counter(snapshot: &[Recorded], name: &str, labels: &[(&str, &str)]) -> u64 {
+        snapshot
+            .iter()
+            .filter(|(key, ..)| key.key().name() == name)
+            .filter(|(key, ..)| {
+                labels.iter().all(|(k, v)| {
+                    key.key()
+                        .labels()
+                        .any(|label| label.key() == *k && label.value() == *v)
+                })
+            })
+            .map(|(.., value)| match value {
+                DebugValue::Counter(n) => *n,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_client_metrics() {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        // The local recorder only sees the metrics of this thread
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        metrics::with_local_recorder(&recorder, || {
+            runtime.block_on(async {
+                let server = MockServer::start().unwrap();
+                server.set(GetSports::PATH, json!({"sports": [], "total": 0}));
+                server.set(GetPeriods::PATH, json!({"periods": []}));
+                server.set(GetLeagues::PATH, json!({"leagues": 1}));
+                server.fail(GetClientBalance::PATH, 200, "");
+
+                let client = server.client("user", "password");
+                let err = client.get(&GetLeagues { sport_id: 29 }).await.unwrap_err();
+                assert!(matches!(err, PinnacleClientError::DecodeJson(..)));
+                let err = client.get(&GetClientBalance).await.unwrap_err();
+                assert!(matches!(err, PinnacleClientError::EmptyJson(_)));
+
+                let store = MemoryCacheStore::new(NonZeroUsize::new(10).unwrap(), Duration::MAX);
+                let client = PinnacleCachingClient::with_client(client, store, Duration::MAX)
+                    .with_policy::<GetSports>(CachePolicy::Forever)
+                    .with_policy::<GetPeriods>(CachePolicy::StaleWhileRevalidate {
+                        ttl: Duration::ZERO,
+                        stale: Duration::from_secs(60),
+                    });
+                for _ in 0..2 {
+                    client.get(&GetSports).await.unwrap();
+                    client.get(&GetPeriods { sport_id: 29 }).await.unwrap();
+                }
+            })
+        });
+
+        // Taking a snapshot resets the counters, so it's taken once
+        let snapshot = snapshotter.snapshot().into_vec();
+        let sports = ("endpoint", GetSports::PATH);
+        let ok = ("status", "200");
+        assert_eq!(counter(&snapshot, REQUESTS_METRIC, &[sports, ok]), 1);
+        let leagues = ("endpoint", GetLeagues::PATH);
+        assert_eq!(counter(&snapshot, REQUESTS_METRIC, &[leagues, ok]), 1);
+
+        let decode = ("error", "decode_json");
+        assert_eq!(
+            counter(&snapshot, DECODE_ERRORS_METRIC, &[leagues, decode]),
+            1
+        );
+        let balance = ("endpoint", GetClientBalance::PATH);
+        let empty = ("error", "empty_json");
+        assert_eq!(
+            counter(&snapshot, DECODE_ERRORS_METRIC, &[balance, empty]),
+            1
+        );
+
+        let periods = ("endpoint", GetPeriods::PATH);
+        let lookups = |labels: &[(&str, &str)]| counter(&snapshot, CACHE_LOOKUPS_METRIC, labels);
+        assert_eq!(lookups(&[sports, ("result", "miss")]), 1);
+        assert_eq!(lookups(&[sports, ("result", "hit")]), 1);
+        assert_eq!(lookups(&[periods, ("result", "miss")]), 1);
+        assert_eq!(lookups(&[periods, ("result", "stale")]), 1);
+
+        #[cfg(feature = "extra-fields")]
+        {
+            let total = [sports, ("field", "total")];
+            assert_eq!(counter(&snapshot, EXTRA_FIELDS_METRIC, &total), 1);
+        }
+    }
+}
@@ -0,0 +1,374 @@
+//! Flat tables of odds and fixtures for analysis.
+//!
+//! Odds are flattened into one [`OddsRow`] per price of a [`MarketBook`], or of the odds alone
+//! when the fixtures aren't at hand, fixtures into one [`FixtureRow`] per event. Rows are
+//! written as CSV or newline-delimited JSON to any [`io::Write`], with the [`Row::COLUMNS`] in
+//! the order of the row fields. CSV requires the `csv` feature.
+//!
+//! ```rust,no_run
+//! # #[cfg(feature = "csv")]
+//! # {
+//! use pinnacle::prelude::*;
+//!
+//! # fn main() -> Result<(), ExportError> {
+//! # let book = MarketBook::new(
+//! #     &serde_json::from_str(r#"{"sportId": 29, "last": 1, "league": []}"#).unwrap(),
+//! #     &serde_json::from_str(r#"{"sportId": 29, "last": 1, "leagues": []}"#).unwrap(),
+//! #     &serde_json::from_str(r#"{"leagues": []}"#).unwrap(),
+//! #     &serde_json::from_str(r#"{"periods": []}"#).unwrap(),
+//! # );
+//! let rows = OddsRow::from_book(&book);
+//! write_csv(&rows, std::io::stdout())?;
+//! write_ndjson(&rows, std::fs::File::create("odds.ndjson")?)?;
+//! # Ok(())
+//! # }
+//! # }
+//! ```
+use crate::market_book::{MarketBook, MarketType, Side};
+use crate::responses::{FixturesResponse, OddsResponse};
+use crate::util::parse_starts;
+use chrono::{DateTime, Utc};
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use thiserror::Error;
+
+/// A price of a selection with its event
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OddsRow {
+    /// Sport id.
+    pub sport_id: i32,
+    /// League id.
+    pub league_id: i32,
+    /// League name.
+    pub league: String,
+    /// Event id.
+    pub event_id: i64,
+    /// Home team name.
+    pub home: String,
+    /// Away team name.
+    pub away: String,
+    /// Start time of the event in UTC.
+    pub starts: Option<DateTime<Utc>>,
+    /// Period number.
+    pub period_number: i32,
+    /// Period description.
+    pub period: Option<String>,
+    /// Market type.
+    pub market_type: MarketType,
+    /// Handicap or points.
+    pub line: Option<f64>,
+    /// Side of the market.
+    pub side: Side,
+    /// The price.
+    pub price: f64,
+    /// Maximum bet volume.
+    pub max: Option<f64>,
+    /// Alternative line id.
+    pub alt_line_id: Option<i64>,
+    /// Date time of the last market update.
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// An event of the fixtures
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FixtureRow {
+    /// Sport id.
+    pub sport_id: i32,
+    /// League id.
+    pub league_id: i32,
+    /// League name.
+    pub league: String,
+    /// Event id.
+    pub event_id: i64,
+    /// Parent event id.
+    pub parent_id: Option<i64>,
+    /// Start time of the event in UTC, if it could be parsed.
+    pub starts: Option<DateTime<Utc>>,
+    /// Home team name.
+    pub home: String,
+    /// Away team name.
+    pub away: String,
+    /// Live status of the event.
+    pub live_status: i32,
+    /// Parlay status of the event.
+    pub parlay_restriction: i32,
+    /// Specifies based on what the event will be resulted, e.g. Corners, Bookings.
+    pub resulting_unit: Option<String>,
+    /// Fixture version.
+    pub version: i64,
+}
+
+/// A flat row with fixed columns
+pub trait Row: Serialize {
+    /// Column names, in the order the fields are serialized
+    const COLUMNS: &'static [&'static str];
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum ExportError {
+    /// csv
+    #[cfg(feature = "csv")]
+    Csv(#[from] csv::Error),
+    /// json
+    Json(#[from] serde_json::Error),
+    /// io
+    Io(#[from] io::Error),
+}
+
+impl OddsRow {
+    /// One row per price of the book
+    pub fn from_book(book: &MarketBook) -> Vec<Self> {
+        book.selections()
+            .map(|s| Self {
+                sport_id: book.sport_id,
+                league_id: s.event.league_id,
+                league: s.event.league.clone(),
+                event_id: s.event.id,
+                home: s.event.home.clone(),
+                away: s.event.away.clone(),
+                starts: Some(s.event.starts),
+                period_number: s.market.number,
+                period: s.market.description.clone(),
+                market_type: s.price.market_type,
+                line: s.price.line,
+                side: s.price.side,
+                price: s.price.price,
+                max: s.price.max,
+                alt_line_id: s.price.alt_line_id,
+                updated_at: s.price.updated_at,
+            })
+            .collect()
+    }
+
+    /// One row per price of the odds, leaving the columns only found in the fixtures, leagues
+    /// and periods empty
+    pub fn from_odds(odds: &OddsResponse) -> Vec<Self> {
+        let mut rows = Vec::new();
+        for league in odds.leagues.iter() {
+            for event in league.events.iter() {
+                for period in event.periods.iter() {
+                    rows.extend(period.prices().into_iter().map(|price| Self {
+                        sport_id: odds.sport_id,
+                        league_id: league.id,
+                        league: String::new(),
+                        event_id: event.id,
+                        home: String::new(),
+                        away: String::new(),
+                        starts: None,
+                        period_number: period.number,
+                        period: None,
+                        market_type: price.market_type,
+                        line: price.line,
+                        side: price.side,
+                        price: price.price,
+                        max: price.max,
+                        alt_line_id: price.alt_line_id,
+                        updated_at: price.updated_at,
+                    }));
+                }
+            }
+        }
+        rows
+    }
+}
+
+impl Row for OddsRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "sport_id",
+        "league_id",
+        "league",
+        "event_id",
+        "home",
+        "away",
+        "starts",
+        "period_number",
+        "period",
+        "market_type",
+        "line",
+        "side",
+        "price",
+        "max",
+        "alt_line_id",
+        "updated_at",
+    ];
+}
+
+impl FixtureRow {
+    /// One row per event of the fixtures
+    pub fn from_fixtures(fixtures: &FixturesResponse) -> Vec<Self> {
+        fixtures
+            .league
+            .iter()
+            .flat_map(|league| {
+                league.events.iter().map(|f| Self {
+                    sport_id: fixtures.sport_id,
+                    league_id: league.id,
+                    league: league.name.clone(),
+                    event_id: f.id,
+                    parent_id: f.parent_id,
+                    starts: parse_starts(&f.starts),
+                    home: f.home.clone(),
+                    away: f.away.clone(),
+                    live_status: f.live_status,
+                    parlay_restriction: f.parlay_restriction,
+                    resulting_unit: f.resulting_unit.clone(),
+                    version: f.version,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Row for FixtureRow {
+    const COLUMNS: &'static [&'static str] = &[
+        "sport_id",
+        "league_id",
+        "league",
+        "event_id",
+        "parent_id",
+        "starts",
+        "home",
+        "away",
+        "live_status",
+        "parlay_restriction",
+        "resulting_unit",
+        "version",
+    ];
+}
+
+/// Writes the rows as CSV with a header, which is written even without rows
+#[cfg(feature = "csv")]
+pub fn write_csv<R: Row>(rows: &[R], writer: impl Write) -> Result<(), ExportError> {
+    let mut csv = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    csv.write_record(R::COLUMNS)?;
+    for row in rows {
+        csv.serialize(row)?;
+    }
+    csv.flush()?;
+    Ok(())
+}
+
+/// Writes the rows as newline-delimited JSON
+pub fn write_ndjson<R: Serialize>(rows: &[R], writer: impl Write) -> Result<(), ExportError> {
+    let mut writer = io::BufWriter::new(writer);
+    for row in rows {
+        serde_json::to_writer(&mut writer, row)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_json;
+
+    #[test]
+    fn test_export_odds() {
+        let fixtures = parse_json(
+            r#"{"sportId": 29, "last": 1, "league": [{"id": 1, "name": "League",
+                "events": [{"id": 10, "starts": "2023-04-16T18:00:00Z", "home": "Home FC",
+                "away": "Away FC", "liveStatus": 0, "parlayRestriction": 0,
+                "altTeaser": false, "version": 1}]}]}"#,
+        )
+        .unwrap();
+        let odds = parse_json(
+            r#"{"sportId": 29, "last": 1, "leagues": [{"id": 1, "events": [{"id": 10,
+                "periods": [{"lineId": 1, "number": 0, "cutoff": "2023-04-16T18:00:00Z",
+                "status": 1, "maxMoneyline": 500, "moneyline": {"home": 2.1, "away": 3.5}}]}]}]}"#,
+        )
+        .unwrap();
+        let periods = parse_json(
+            r#"{"periods": [{"number": 0, "description": "Match", "shortDescription": "M",
+                "spreadDescription": "", "moneylineDescription": "", "totalDescription": "",
+                "team1TotalDescription": "", "team2TotalDescription": "",
+                "spreadShortDescription": "", "moneylineShortDescription": "",
+                "totalShortDescription": "", "team1TotalShortDescription": "",
+                "team2TotalShortDescription": ""}]}"#,
+        )
+        .unwrap();
+        let leagues = parse_json(r#"{"leagues": []}"#).unwrap();
+        let book = MarketBook::new(&fixtures, &odds, &leagues, &periods);
+        let rows = OddsRow::from_book(&book);
+        assert_eq!(rows.len(), 2);
+        let home = rows.iter().find(|r| r.side == Side::Home).unwrap();
+        assert_eq!(home.event_id, 10);
+        assert_eq!(home.league, "League");
+        assert_eq!(home.home, "Home FC");
+        assert_eq!(home.market_type, MarketType::Moneyline);
+        assert_eq!(home.price, 2.1);
+        assert_eq!(home.max, Some(500.0));
+        assert_eq!(home.period.as_deref(), Some("Match"));
+
+        // Without the fixtures the rows are kept, with the team columns empty
+        let rows = OddsRow::from_odds(&odds);
+        assert_eq!(rows.len(), 2);
+        let home = rows.iter().find(|r| r.side == Side::Home).unwrap();
+        assert_eq!((home.event_id, home.league_id, home.price), (10, 1, 2.1));
+        assert_eq!((home.home.as_str(), home.away.as_str()), ("", ""));
+        assert_eq!(home.starts, None);
+
+        #[cfg(feature = "csv")]
+        {
+            let mut csv = Vec::new();
+            write_csv(&rows, &mut csv).unwrap();
+            let csv = String::from_utf8(csv).unwrap();
+            assert_eq!(
+                csv.lines().next(),
+                Some(OddsRow::COLUMNS.join(",").as_str())
+            );
+            assert_eq!(
+                csv.lines().nth(1),
+                Some("29,1,,10,,,,0,,Moneyline,,Home,2.1,500.0,,")
+            );
+        }
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_csv_header_without_rows() {
+        let mut csv = Vec::new();
+        write_csv::<FixtureRow>(&[], &mut csv).unwrap();
+        assert_eq!(
+            String::from_utf8(csv).unwrap(),
+            "sport_id,league_id,league,event_id,parent_id,starts,home,away,live_status,\
+            parlay_restriction,resulting_unit,version\n"
+        );
+    }
+
+    #[test]
+    fn test_export_fixtures() {
+        let fixtures: FixturesResponse = parse_json(
+            r#"{"sportId": 29, "last": 1, "league": [{"id": 1, "name": "League",
+                "events": [{"id": 10, "starts": "2023-04-16T18:00:00Z", "home": "Home FC",
+                "away": "Away, FC", "liveStatus": 0, "parlayRestriction": 0,
+                "altTeaser": false, "version": 1}]}]}"#,
+        )
+        .unwrap();
+        let rows = FixtureRow::from_fixtures(&fixtures);
+
+        #[cfg(feature = "csv")]
+        {
+            let mut csv = Vec::new();
+            write_csv(&rows, &mut csv).unwrap();
+            assert_eq!(
+                String::from_utf8(csv).unwrap(),
+                "sport_id,league_id,league,event_id,parent_id,starts,home,away,live_status,\
+                parlay_restriction,resulting_unit,version\n\
+                29,1,League,10,,2023-04-16T18:00:00Z,Home FC,\"Away, FC\",0,0,,1\n"
+            );
+        }
+
+        let mut ndjson = Vec::new();
+        write_ndjson(&rows, &mut ndjson).unwrap();
+        let ndjson = String::from_utf8(ndjson).unwrap();
+        assert_eq!(ndjson.lines().count(), 1);
+        let row: FixtureRow = parse_json(ndjson.trim()).unwrap();
+        assert_eq!(row, rows[0]);
+    }
+}
@@ -49,14 +49,56 @@
 //! # }
 //! ```
 //!
+//! ## Command line
+//!
+//! With the `cli` feature the crate also builds the `pinnacle` binary covering all the wrapped
+//! requests:
+//!
+//! ```sh
+//! cargo install pinnacle --features cli
+//! export PINNACLE_USERNAME=... PINNACLE_PASSWORD=...
+//! pinnacle --output csv odds 29 --league-ids 1980,2627 --odds-format decimal
+//! pinnacle watch --sport 29 --league 1980 --interval 5
+//! ```
+//!
+//! The `mock-server` feature builds `pinnacle-mock`, a local stand-in of the API serving json
+//! files, to run the client and the code built on it against in integration tests.
+//!
 //! [api]: https://pinnacleapi.github.io/
 
 #![warn(clippy::all, missing_docs, nonstandard_style, future_incompatible)]
 
+#[cfg(feature = "arrow")]
+pub mod arrow_export;
+pub mod bet_guard;
+pub mod bet_submitter;
+#[cfg(feature = "blocking")]
+pub mod blocking_client;
+pub mod cache_store;
 pub mod caching_client;
 pub mod client;
+#[cfg(feature = "metrics")]
+pub mod client_metrics;
+pub mod export;
+pub mod market_book;
+#[cfg(feature = "tower")]
+pub mod middleware;
+pub mod mock_client;
+#[cfg(feature = "mock-server")]
+pub mod mock_server;
+pub mod odds_diff;
+pub mod odds_recorder;
 pub mod prelude;
+pub mod replay_client;
 pub mod requests;
 pub mod responses;
+pub mod schema_check;
+pub mod selection;
+pub mod single_flight;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_cache_store;
 pub mod traits;
 pub mod util;
+
+#[cfg(feature = "tower")]
+pub use tower;
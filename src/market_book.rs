@@ -0,0 +1,340 @@
+//! Fixtures joined with odds into a single view of priced events.
+//!
+//! [`OddsResponse`] only knows event ids, while team names and start times come from
+//! [`FixturesResponse`]. [`MarketBook`] does the join once, adding league names from [`Leagues`]
+//! and period descriptions from [`SportPeriods`].
+use crate::responses::*;
+use crate::util::parse_starts;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Odds of a sport joined with its fixtures
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MarketBook {
+    /// Sport id.
+    pub sport_id: i32,
+    /// Events having both fixtures and odds.
+    pub events: Vec<Event>,
+}
+
+/// An event with its markets
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Event {
+    /// Event id.
+    pub id: i64,
+    /// League id.
+    pub league_id: i32,
+    /// League name.
+    pub league: String,
+    /// Home team name.
+    pub home: String,
+    /// Away team name.
+    pub away: String,
+    /// Start time of the event in UTC.
+    pub starts: DateTime<Utc>,
+    /// Markets of the event, one per period.
+    pub periods: Vec<Market>,
+}
+
+/// All the prices offered for a period of an event
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Market {
+    /// Period number.
+    pub number: i32,
+    /// Period description, e.g. "1st Half", if it's found in the sport periods.
+    pub description: Option<String>,
+    /// Line id.
+    pub line_id: i64,
+    /// Period’s wagering cut-off date in UTC.
+    pub cutoff: DateTime<Utc>,
+    /// 1 - online, period is open for betting. 2 - offline, period is not open for betting.
+    pub status: i32,
+    /// Prices of the period.
+    pub prices: Vec<Price>,
+}
+
+/// Market type of a price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum MarketType {
+    /// Moneyline
+    Moneyline,
+    /// Spread (handicap)
+    Spread,
+    /// Total points
+    Total,
+    /// Home team total points
+    HomeTeamTotal,
+    /// Away team total points
+    AwayTeamTotal,
+}
+
+/// Side of a market the price is offered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Side {
+    /// Home team
+    Home,
+    /// Away team
+    Away,
+    /// Draw
+    Draw,
+    /// Over the total
+    Over,
+    /// Under the total
+    Under,
+}
+
+/// A single price of a period
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Price {
+    /// Market type.
+    pub market_type: MarketType,
+    /// Side of the market.
+    pub side: Side,
+    /// Handicap of the side for spreads, points for totals, `None` for moneyline.
+    pub line: Option<f64>,
+    /// The price.
+    pub price: f64,
+    /// This is present only if it's an alternative line.
+    pub alt_line_id: Option<i64>,
+    /// Maximum bet volume: the alternative line limit if present, the period market limit
+    /// otherwise.
+    pub max: Option<f64>,
+    /// Date time of the last market update.
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// A price along with the event and market it belongs to
+#[derive(Debug, Clone, Copy)]
+pub struct PricedSelection<'a> {
+    /// The event.
+    pub event: &'a Event,
+    /// The period market.
+    pub market: &'a Market,
+    /// The price.
+    pub price: &'a Price,
+}
+
+impl MarketBook {
+    /// Joins odds with fixtures, leagues and periods of the same sport. Events without a fixture
+    /// are skipped as there's no way to tell their teams.
+    pub fn new(
+        fixtures: &FixturesResponse,
+        odds: &OddsResponse,
+        leagues: &Leagues,
+        periods: &SportPeriods,
+    ) -> Self {
+        let fixtures: HashMap<i64, (&FixturesLeague, &Fixture)> = fixtures
+            .league
+            .iter()
+            .flat_map(|league| league.events.iter().map(move |f| (f.id, (league, f))))
+            .collect();
+        let league_names: HashMap<i32, &str> = leagues
+            .leagues
+            .iter()
+            .map(|l| (l.id, l.name.as_str()))
+            .collect();
+        let period_names: HashMap<i32, &str> = periods
+            .periods
+            .iter()
+            .map(|p| (p.number, p.description.as_str()))
+            .collect();
+
+        let mut events = Vec::new();
+        for odds_league in odds.leagues.iter() {
+            for odds_event in odds_league.events.iter() {
+                let Some((fixtures_league, fixture)) = fixtures.get(&odds_event.id) else {
+                    continue;
+                };
+                let Some(starts) = parse_starts(&fixture.starts) else {
+                    continue;
+                };
+                let league = league_names
+                    .get(&odds_league.id)
+                    .copied()
+                    .unwrap_or(&fixtures_league.name);
+                let periods = odds_event
+                    .periods
+                    .iter()
+                    .map(|p| Market {
+                        number: p.number,
+                        description: period_names.get(&p.number).map(|s| s.to_string()),
+                        line_id: p.line_id,
+                        cutoff: p.cutoff,
+                        status: p.status,
+                        prices: p.prices(),
+                    })
+                    .collect();
+                events.push(Event {
+                    id: odds_event.id,
+                    league_id: odds_league.id,
+                    league: league.into(),
+                    home: fixture.home.clone(),
+                    away: fixture.away.clone(),
+                    starts,
+                    periods,
+                });
+            }
+        }
+
+        Self {
+            sport_id: odds.sport_id,
+            events,
+        }
+    }
+
+    /// Returns an event by its id
+    pub fn event(&self, id: i64) -> Option<&Event> {
+        self.events.iter().find(|e| e.id == id)
+    }
+
+    /// Iterates over every price of every event
+    pub fn selections(&self) -> impl Iterator<Item = PricedSelection<'_>> {
+        self.events.iter().flat_map(|event| {
+            event.periods.iter().flat_map(move |market| {
+                market.prices.iter().map(move |price| PricedSelection {
+                    event,
+                    market,
+                    price,
+                })
+            })
+        })
+    }
+}
+
+impl Market {
+    /// Whether the period is open for betting
+    pub fn is_online(&self) -> bool {
+        self.status == 1
+    }
+}
+
+impl OddsPeriod {
+    /// Flattens the period into a list of prices
+    pub fn prices(&self) -> Vec<Price> {
+        use MarketType::*;
+        use Side::*;
+
+        let mut prices = Vec::new();
+        let mut push = |market_type, sides: &[(Side, Option<f64>, f64)], alt_line_id, max, at| {
+            for &(side, line, price) in sides {
+                prices.push(Price {
+                    market_type,
+                    side,
+                    line,
+                    price,
+                    alt_line_id,
+                    max,
+                    updated_at: at,
+                });
+            }
+        };
+
+        if let Some(ml) = &self.moneyline {
+            let (max, at) = (self.max_moneyline, self.moneyline_updated_at);
+            push(
+                Moneyline,
+                &[(Home, None, ml.home), (Away, None, ml.away)],
+                None,
+                max,
+                at,
+            );
+            if let Some(draw) = ml.draw {
+                push(Moneyline, &[(Draw, None, draw)], None, max, at);
+            }
+        }
+        for s in self.spreads.iter().flatten() {
+            let sides = [(Home, Some(s.hdp), s.home), (Away, Some(-s.hdp), s.away)];
+            let max = s.max.or(self.max_spread);
+            push(Spread, &sides, s.alt_line_id, max, self.spread_updated_at);
+        }
+        for t in self.totals.iter().flatten() {
+            let sides = [
+                (Over, Some(t.points), t.over),
+                (Under, Some(t.points), t.under),
+            ];
+            let max = t.max.or(self.max_total);
+            push(Total, &sides, t.alt_line_id, max, self.total_updated_at);
+        }
+        if let Some(tt) = &self.team_total {
+            let (max, at) = (self.max_team_total, self.team_total_updated_at);
+            for (market_type, total) in [(HomeTeamTotal, &tt.home), (AwayTeamTotal, &tt.away)] {
+                let Some(t) = total else { continue };
+                let sides = [
+                    (Over, Some(t.points), t.over),
+                    (Under, Some(t.points), t.under),
+                ];
+                push(market_type, &sides, None, max, at);
+            }
+        }
+        prices
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_json;
+
+    fn book() -> MarketBook {
+        let fixtures: FixturesResponse = parse_json(
+            r#"{"sportId": 29, "last": 1, "league": [{"id": 1, "name": "Fixtures League",
+                "events": [{"id": 10, "starts": "2023-04-16T18:00:00Z", "home": "Home FC",
+                "away": "Away FC", "liveStatus": 0, "parlayRestriction": 0, "altTeaser": false,
+                "version": 1}]}]}"#,
+        )
+        .unwrap();
+        let odds: OddsResponse = parse_json(
+            r#"{"sportId": 29, "last": 2, "leagues": [{"id": 1, "events": [
+                {"id": 10, "periods": [{"lineId": 100, "number": 0,
+                    "cutoff": "2023-04-16T18:00:00Z", "status": 1, "maxSpread": 500,
+                    "maxMoneyline": 300, "moneyline": {"home": 2.1, "away": 3.5, "draw": 3.2},
+                    "spreads": [{"hdp": -0.5, "home": 2.0, "away": 1.9},
+                        {"altLineId": 7, "hdp": -1.0, "home": 2.5, "away": 1.5, "max": 100}],
+                    "teamTotal": {"home": {"points": 1.5, "over": 2.2, "under": 1.7}}}]},
+                {"id": 11, "periods": []}]}]}"#,
+        )
+        .unwrap();
+        let leagues: Leagues = parse_json(r#"{"leagues": []}"#).unwrap();
+        let periods: SportPeriods = parse_json(
+            r#"{"periods": [{"number": 0, "description": "Match", "shortDescription": "M",
+                "spreadDescription": "", "moneylineDescription": "", "totalDescription": "",
+                "team1TotalDescription": "", "team2TotalDescription": "",
+                "spreadShortDescription": "", "moneylineShortDescription": "",
+                "totalShortDescription": "", "team1TotalShortDescription": "",
+                "team2TotalShortDescription": ""}]}"#,
+        )
+        .unwrap();
+        MarketBook::new(&fixtures, &odds, &leagues, &periods)
+    }
+
+    #[test]
+    fn test_market_book() {
+        let book = book();
+        assert_eq!(book.events.len(), 1, "events without fixtures are skipped");
+
+        let event = book.event(10).unwrap();
+        assert_eq!(event.league, "Fixtures League");
+        assert_eq!(event.home, "Home FC");
+        assert_eq!(event.periods[0].description.as_deref(), Some("Match"));
+
+        let prices: Vec<_> = book
+            .selections()
+            .map(|s| (s.price.market_type, s.price.side, s.price.line, s.price.max))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![
+                (MarketType::Moneyline, Side::Home, None, Some(300.0)),
+                (MarketType::Moneyline, Side::Away, None, Some(300.0)),
+                (MarketType::Moneyline, Side::Draw, None, Some(300.0)),
+                (MarketType::Spread, Side::Home, Some(-0.5), Some(500.0)),
+                (MarketType::Spread, Side::Away, Some(0.5), Some(500.0)),
+                (MarketType::Spread, Side::Home, Some(-1.0), Some(100.0)),
+                (MarketType::Spread, Side::Away, Some(1.0), Some(100.0)),
+                (MarketType::HomeTeamTotal, Side::Over, Some(1.5), None),
+                (MarketType::HomeTeamTotal, Side::Under, Some(1.5), None),
+            ]
+        );
+    }
+}
@@ -0,0 +1,933 @@
+//! Transport as a [`tower::Service`] stack, requires the `tower` feature.
+//!
+//! [`HttpService`] sends the requests, the layers of this module add caching, retries, rate
+//! limiting, timeouts, coalescing, logging and metrics on top of it in any order, along with
+//! any other layer serving [`ApiRequest`] with [`RawResponse`]. [`ServiceClient`] turns the stack into a
+//! typed [`PinnacleApiClient`].
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//! use std::num::NonZeroUsize;
+//! use std::time::Duration;
+//! use tower::ServiceBuilder;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let store = MemoryCacheStore::new(NonZeroUsize::new(1000).unwrap(), Duration::from_secs(600));
+//! let service = ServiceBuilder::new()
+//!     .layer(LoggingLayer::new())
+//!     .layer(CacheLayer::new(store, Duration::from_secs(60)))
+//!     .layer(SingleFlightLayer::new())
+//!     .layer(RetryLayer::new(3, Duration::from_millis(500)))
+//!     .layer(RateLimitLayer::new(Duration::from_secs(1)).per_endpoint())
+//!     .layer(TimeoutLayer::new(Duration::from_secs(10)))
+//!     .service(HttpService::new(PinnacleClient::new("user", "password")));
+//! let client = ServiceClient::new(service);
+//! let sports = client.get(&GetSports).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::CacheStore;
+use crate::caching_client::{lock, CachePolicy, Freshness, ResponseCache};
+use crate::client::{PinnacleClient, PinnacleClientError, RawResponse};
+use crate::single_flight::InFlightRequests;
+use crate::traits::{PinnacleApiClient, PinnacleApiRequest};
+use crate::util::error_chain;
+use async_trait::async_trait;
+use reqwest::{IntoUrl, Method, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
+use tower::{Layer, Service, ServiceExt};
+
+/// Future returned by the services of the stack
+pub type ApiFuture<T> = Pin<Box<dyn Future<Output = Result<T, PinnacleClientError>> + Send>>;
+
+/// A request to the API
+#[derive(Debug, Clone)]
+pub struct ApiRequest {
+    /// HTTP method, the API only uses GET and POST
+    pub method: Method,
+    /// Full URL
+    pub url: Url,
+    /// Json body of a POST request
+    pub body: Option<String>,
+}
+
+impl ApiRequest {
+    /// GET request
+    pub fn get(url: Url) -> Self {
+        Self {
+            method: Method::GET,
+            url,
+            body: None,
+        }
+    }
+
+    /// POST request with an encoded json body
+    pub fn post(url: Url, body: String) -> Self {
+        Self {
+            method: Method::POST,
+            url,
+            body: Some(body),
+        }
+    }
+
+    fn is_get(&self) -> bool {
+        self.method == Method::GET
+    }
+}
+
+/// The innermost service sending the requests with a [`PinnacleClient`]
+#[derive(Debug, Clone)]
+pub struct HttpService {
+    client: Arc<PinnacleClient>,
+}
+
+impl HttpService {
+    /// Wraps the client
+    pub fn new(client: PinnacleClient) -> Self {
+        Self {
+            client: Arc::new(client),
+        }
+    }
+}
+
+impl Service<ApiRequest> for HttpService {
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let client = self.client.clone();
+        Box::pin(async move {
+            match req.body {
+                Some(body) if req.method == Method::POST => client.post_raw(req.url, body).await,
+                _ => client.get_raw(req.url).await,
+            }
+        })
+    }
+}
+
+/// Typed API client on top of a service stack
+#[derive(Debug, Clone)]
+pub struct ServiceClient<S> {
+    service: S,
+}
+
+impl<S> ServiceClient<S> {
+    /// Wraps the service
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+
+    /// The wrapped service
+    pub fn service(&self) -> &S {
+        &self.service
+    }
+}
+
+#[async_trait]
+impl<S> PinnacleApiClient for ServiceClient<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + Sync
+        + 'static,
+    S::Future: Send,
+{
+    type Error = PinnacleClientError;
+
+    async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        self.get_raw(url.clone()).await?.parse(&url)
+    }
+
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        let req = ApiRequest::get(url.into_url()?);
+        self.service.clone().oneshot(req).await
+    }
+
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        let body = serde_json::to_string(body)
+            .map_err(|e| PinnacleClientError::EncodeJson(e, url.clone()))?;
+        let req = ApiRequest::post(url.clone(), body);
+        self.service.clone().oneshot(req).await?.parse(&url)
+    }
+}
+
+/// Fails requests taking longer than the duration with [`PinnacleClientError::Timeout`]
+#[derive(Debug, Clone, Copy)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    /// Creates the layer
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+/// Service of the [`TimeoutLayer`]
+#[derive(Debug, Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S> Service<ApiRequest> for Timeout<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let timeout = self.timeout;
+        Box::pin(async move {
+            let url = req.url.clone();
+            tokio::time::timeout(timeout, inner.oneshot(req))
+                .await
+                .unwrap_or(Err(PinnacleClientError::Timeout(url)))
+        })
+    }
+}
+
+/// Retries GET requests which timed out or got a 429 or 5xx response, waiting exponentially
+/// longer between the attempts, up to the max backoff, or as long as the `Retry-After` header
+/// says
+#[derive(Debug, Clone, Copy)]
+pub struct RetryLayer {
+    max_retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryLayer {
+    /// Creates the layer retrying up to `max_retries` times, waiting `backoff` before the first
+    /// retry and at most a minute before the others
+    pub fn new(max_retries: u32, backoff: Duration) -> Self {
+        Self {
+            max_retries,
+            backoff,
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+
+    /// Sets the longest wait between the attempts when there's no `Retry-After` header
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Service of the [`RetryLayer`]
+#[derive(Debug, Clone)]
+pub struct Retry<S> {
+    inner: S,
+    layer: RetryLayer,
+}
+
+impl<S> Service<ApiRequest> for Retry<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let RetryLayer {
+            max_retries,
+            backoff,
+            max_backoff,
+        } = self.layer;
+        let mut backoff = backoff.min(max_backoff);
+        Box::pin(async move {
+            if !req.is_get() {
+                return inner.oneshot(req).await;
+            }
+            let mut attempt = 0;
+            loop {
+                let result = inner.clone().oneshot(req.clone()).await;
+                let delay = match &result {
+                    Ok(raw) if raw.status == 429 || (500..600).contains(&raw.status) => {
+                        retry_after(raw).unwrap_or(backoff)
+                    }
+                    Err(e) if e.is_timeout() => backoff,
+                    _ => return result,
+                };
+                if attempt >= max_retries {
+                    return result;
+                }
+                attempt += 1;
+                eprintln!("Retrying {} in {delay:?}", req.url);
+                #[cfg(feature = "metrics")]
+                crate::client_metrics::record_retry(&req.url);
+                tokio::time::sleep(delay).await;
+                backoff = backoff.saturating_mul(2).min(max_backoff);
+            }
+        })
+    }
+}
+
+/// Delay requested by the `Retry-After` header in seconds
+fn retry_after(raw: &RawResponse) -> Option<Duration> {
+    let secs = raw.headers.get("retry-after")?.trim().parse().ok()?;
+    Some(Duration::from_secs(secs))
+}
+
+/// Spaces the requests at least `min_interval` apart, globally or per endpoint
+///
+/// A request reserves its slot when its future is first polled. A future dropped while waiting
+/// for its slot keeps it reserved, so the requests after it still wait.
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    min_interval: Duration,
+    per_endpoint: bool,
+    next_slots: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl RateLimitLayer {
+    /// Creates the layer with a single limit shared by all the endpoints
+    pub fn new(min_interval: Duration) -> Self {
+        Self {
+            min_interval,
+            per_endpoint: false,
+            next_slots: Default::default(),
+        }
+    }
+
+    /// Limits each endpoint path separately
+    pub fn per_endpoint(mut self) -> Self {
+        self.per_endpoint = true;
+        self
+    }
+
+    /// Reserves the next free slot of the url, returning when it starts
+    fn reserve(&self, url: &Url) -> Instant {
+        let key = if self.per_endpoint { url.path() } else { "" };
+        let now = Instant::now();
+        let mut next_slots = lock(&self.next_slots);
+        let next = next_slots.entry(key.to_string()).or_insert(now);
+        let slot = (*next).max(now);
+        *next = slot + self.min_interval;
+        slot
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service of the [`RateLimitLayer`]
+#[derive(Debug, Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S> Service<ApiRequest> for RateLimit<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        let layer = self.layer.clone();
+        Box::pin(async move {
+            let slot = layer.reserve(&req.url);
+            #[cfg(feature = "metrics")]
+            crate::client_metrics::record_rate_limit_wait(
+                &req.url,
+                slot.saturating_duration_since(Instant::now()),
+            );
+            tokio::time::sleep_until(slot).await;
+            inner.oneshot(req).await
+        })
+    }
+}
+
+/// Logs the requests to stderr with their status and duration
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingLayer;
+
+impl LoggingLayer {
+    /// Creates the layer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for LoggingLayer {
+    type Service = Logging<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Logging { inner }
+    }
+}
+
+/// Service of the [`LoggingLayer`]
+#[derive(Debug, Clone)]
+pub struct Logging<S> {
+    inner: S,
+}
+
+impl<S> Service<ApiRequest> for Logging<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let (method, url) = (req.method.clone(), req.url.clone());
+            let started = Instant::now();
+            let result = inner.oneshot(req).await;
+            let elapsed = started.elapsed();
+            match &result {
+                Ok(raw) => eprintln!("{method} {url} {} in {elapsed:?}", raw.status),
+                Err(e) => eprintln!(
+                    "{method} {url} failed in {elapsed:?} <-- {}",
+                    error_chain(e)
+                ),
+            }
+            result
+        })
+    }
+}
+
+/// Records the requests passing through it with the [`metrics`] facade, requires the `metrics`
+/// feature. Placed above a [`CacheLayer`] it counts the cached responses too, see
+/// [`client_metrics`](crate::client_metrics) for the metric names.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MetricsLayer;
+
+#[cfg(feature = "metrics")]
+impl MetricsLayer {
+    /// Creates the layer
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics { inner }
+    }
+}
+
+/// Service of the [`MetricsLayer`]
+#[cfg(feature = "metrics")]
+#[derive(Debug, Clone)]
+pub struct Metrics<S> {
+    inner: S,
+}
+
+#[cfg(feature = "metrics")]
+impl<S> Service<ApiRequest> for Metrics<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        Box::pin(async move {
+            let (method, url) = (req.method.clone(), req.url.clone());
+            let started = Instant::now();
+            let result = inner.oneshot(req).await;
+            crate::client_metrics::record_service_request(
+                method.as_str(),
+                &url,
+                result.as_ref().ok().map(|raw| raw.status),
+                started.elapsed(),
+            );
+            result
+        })
+    }
+}
+
+/// Caches GET responses in a [`CacheStore`] with the policies of
+/// [`PinnacleCachingClient`](crate::caching_client::PinnacleCachingClient)
+#[derive(Debug)]
+pub struct CacheLayer<St> {
+    cache: ResponseCache<St>,
+}
+
+impl<St> Clone for CacheLayer<St> {
+    fn clone(&self) -> Self {
+        Self {
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<St: CacheStore + 'static> CacheLayer<St> {
+    /// Creates the layer caching responses in the `store` for `cache_ttl`
+    pub fn new(store: St, cache_ttl: Duration) -> Self {
+        Self {
+            cache: ResponseCache::new(store, cache_ttl),
+        }
+    }
+
+    /// Sets the cache policy for endpoints without their own policy
+    pub fn with_default_policy(mut self, policy: CachePolicy) -> Self {
+        self.cache.set_default_policy(policy);
+        self
+    }
+
+    /// Sets the cache policy for the request type
+    pub fn with_policy<Q: PinnacleApiRequest>(self, policy: CachePolicy) -> Self {
+        self.with_path_policy(Q::PATH, policy)
+    }
+
+    /// Sets the cache policy for the endpoint path, e.g. `/v1/odds`
+    pub fn with_path_policy(mut self, path: impl Into<String>, policy: CachePolicy) -> Self {
+        self.cache.set_policy(path.into(), policy);
+        self
+    }
+}
+
+impl<S, St> Layer<S> for CacheLayer<St> {
+    type Service = Cache<S, St>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Cache {
+            inner,
+            cache: Arc::new(self.cache.clone()),
+        }
+    }
+}
+
+/// Service of the [`CacheLayer`]
+#[derive(Debug)]
+pub struct Cache<S, St> {
+    inner: S,
+    cache: Arc<ResponseCache<St>>,
+}
+
+impl<S: Clone, St> Clone for Cache<S, St> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+impl<S, St> Service<ApiRequest> for Cache<S, St>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+    St: CacheStore + 'static,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        if !req.is_get() {
+            return Box::pin(inner.oneshot(req));
+        }
+        let url = req.url.clone();
+        let policy = self.cache.policy(&url);
+        if let Some((entry, freshness)) = self.cache.lookup(&url, policy) {
+            let fetch = inner.clone().oneshot(ApiRequest::get(url.clone()));
+            // Without a runtime to refresh it in background the stale response is refreshed
+            // inline
+            if freshness == Freshness::Fresh || self.cache.revalidate(&url, fetch) {
+                return Box::pin(async move { Ok(entry.into()) });
+            }
+        }
+        let cache = self.cache.clone();
+        Box::pin(async move {
+            let raw = inner.oneshot(req).await?;
+            cache.save(&url, policy, &raw);
+            Ok(raw)
+        })
+    }
+}
+
+/// Coalesces concurrent identical GET requests into one, like
+/// [`PinnacleSingleFlightClient`](crate::single_flight::PinnacleSingleFlightClient)
+#[derive(Debug, Clone, Default)]
+pub struct SingleFlightLayer {
+    in_flight: Arc<InFlightRequests>,
+}
+
+impl SingleFlightLayer {
+    /// Creates the layer
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<S> Layer<S> for SingleFlightLayer {
+    type Service = SingleFlight<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SingleFlight {
+            inner,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+/// Service of the [`SingleFlightLayer`]
+#[derive(Debug, Clone)]
+pub struct SingleFlight<S> {
+    inner: S,
+    in_flight: Arc<InFlightRequests>,
+}
+
+impl<S> Service<ApiRequest> for SingleFlight<S>
+where
+    S: Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send,
+{
+    type Response = RawResponse;
+    type Error = PinnacleClientError;
+    type Future = ApiFuture<RawResponse>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: ApiRequest) -> Self::Future {
+        let inner = self.inner.clone();
+        if !req.is_get() {
+            return Box::pin(inner.oneshot(req));
+        }
+        let in_flight = self.in_flight.clone();
+        Box::pin(async move {
+            let url = req.url.clone();
+            in_flight.coalesce(url, || inner.oneshot(req)).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache_store::MemoryCacheStore;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceBuilder;
+
+    #[tokio::test]
+    async fn test_service_stack() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let fake = {
+            let calls = calls.clone();
+            tower::service_fn(move |req: ApiRequest| {
+                let n = calls.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if req.url.path() == "/slow" {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                    }
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                    let status = if n == 0 { 503 } else { 200 };
+                    Ok::<_, PinnacleClientError>(RawResponse {
+                        status,
+                        headers: Default::default(),
+                        body: r#"{"ok":true}"#.into(),
+                    })
+                }
+            })
+        };
+        let store = MemoryCacheStore::new(NonZeroUsize::new(10).unwrap(), Duration::from_secs(60));
+        let service = ServiceBuilder::new()
+            .layer(CacheLayer::new(store, Duration::from_secs(60)))
+            .layer(SingleFlightLayer::new())
+            .layer(RetryLayer::new(1, Duration::from_millis(1)))
+            .layer(TimeoutLayer::new(Duration::from_millis(100)))
+            .service(fake);
+        let client = ServiceClient::new(service);
+
+        // The first call fails and is retried, the concurrent one waits for it
+        let url = "https://api.pinnacle.com/v1/sports";
+        let (a, b) = tokio::join!(client.get_raw(url), client.get_raw(url));
+        assert_eq!(a.unwrap().status, 200);
+        assert_eq!(b.unwrap().status, 200);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Cached
+        let data: serde_json::Value = client.get_by_url(url).await.unwrap();
+        assert_eq!(data["ok"], true);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let err = client
+            .get_raw("https://api.pinnacle.com/slow")
+            .await
+            .unwrap_err();
+        assert!(err.is_timeout());
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    /// Paths of the requests with the time they came
+    type Calls = Arc<Mutex<Vec<(String, Instant)>>>;
+
+    /// Responds to the first request with the status and the headers, then with 200, recording
+    /// the requests into `calls`
+    fn recording(
+        status: u16,
+        headers: &[(&str, &str)],
+        calls: Calls,
+    ) -> impl Service<ApiRequest, Response = RawResponse, Error = PinnacleClientError, Future: Send>
+           + Clone
+           + Send
+           + 'static {
+        let headers: std::collections::BTreeMap<_, _> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        tower::service_fn(move |req: ApiRequest| {
+            let n = {
+                let mut calls = lock(&calls);
+                calls.push((req.url.path().to_string(), Instant::now()));
+                calls.len()
+            };
+            let status = if n == 1 { status } else { 200 };
+            let headers = headers.clone();
+            async move {
+                Ok::<_, PinnacleClientError>(RawResponse {
+                    status,
+                    headers,
+                    body: "{}".into(),
+                })
+            }
+        })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit() {
+        let secs = Duration::from_secs;
+        let started = Instant::now();
+        let odds = "https://api.pinnacle.com/v1/odds";
+        let fixtures = "https://api.pinnacle.com/v1/fixtures";
+
+        let calls = Calls::default();
+        let service = recording(200, &[], calls.clone());
+        let client = ServiceClient::new(RateLimitLayer::new(secs(1)).layer(service));
+        let (a, b, c) = tokio::join!(
+            client.get_raw(odds),
+            client.get_raw(fixtures),
+            client.get_raw(odds)
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        let waits: Vec<_> = lock(&calls).iter().map(|(_, t)| *t - started).collect();
+        assert_eq!(waits, [secs(0), secs(1), secs(2)]);
+
+        let started = Instant::now();
+        let calls = Calls::default();
+        let service = recording(200, &[], calls.clone());
+        let layer = RateLimitLayer::new(secs(1)).per_endpoint();
+        let client = ServiceClient::new(layer.layer(service));
+        let (a, b, c) = tokio::join!(
+            client.get_raw(odds),
+            client.get_raw(fixtures),
+            client.get_raw(odds)
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        let mut waits: Vec<_> = lock(&calls)
+            .iter()
+            .map(|(path, t)| (path.clone(), *t - started))
+            .collect();
+        waits.sort();
+        assert_eq!(
+            waits,
+            [
+                ("/v1/fixtures".into(), secs(0)),
+                ("/v1/odds".into(), secs(0)),
+                ("/v1/odds".into(), secs(1)),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry_after() {
+        let started = Instant::now();
+        let calls = Calls::default();
+        let service = recording(429, &[("retry-after", "5")], calls.clone());
+        let layer = RetryLayer::new(1, Duration::from_millis(1));
+        let client = ServiceClient::new(layer.layer(service));
+        let raw = client
+            .get_raw("https://api.pinnacle.com/v1/odds")
+            .await
+            .unwrap();
+        assert_eq!(raw.status, 200);
+        let waits: Vec<_> = lock(&calls).iter().map(|(_, t)| *t - started).collect();
+        assert_eq!(waits, [Duration::ZERO, Duration::from_secs(5)]);
+
+        // Without the header the backoff is used
+        let started = Instant::now();
+        let calls = Calls::default();
+        let service = recording(503, &[], calls.clone());
+        let client = ServiceClient::new(layer.layer(service));
+        client
+            .get_raw("https://api.pinnacle.com/v1/odds")
+            .await
+            .unwrap();
+        let waits: Vec<_> = lock(&calls).iter().map(|(_, t)| *t - started).collect();
+        assert_eq!(waits, [Duration::ZERO, Duration::from_millis(1)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_backoff() {
+        let secs = Duration::from_secs;
+        let started = Instant::now();
+        let calls = Calls::default();
+        let unavailable = {
+            let calls = calls.clone();
+            tower::service_fn(move |req: ApiRequest| {
+                lock(&calls).push((req.url.path().to_string(), Instant::now()));
+                async {
+                    Ok::<_, PinnacleClientError>(RawResponse {
+                        status: 503,
+                        headers: Default::default(),
+                        body: "{}".into(),
+                    })
+                }
+            })
+        };
+        let layer = RetryLayer::new(3, secs(4)).with_max_backoff(secs(10));
+        let client = ServiceClient::new(layer.layer(unavailable.clone()));
+        let raw = client
+            .get_raw("https://api.pinnacle.com/v1/odds")
+            .await
+            .unwrap();
+        assert_eq!(raw.status, 503);
+        let waits: Vec<_> = lock(&calls).iter().map(|(_, t)| *t - started).collect();
+        assert_eq!(waits, [secs(0), secs(4), secs(12), secs(22)]);
+
+        // Doubling a huge backoff doesn't overflow
+        let layer = RetryLayer::new(3, Duration::MAX).with_max_backoff(Duration::MAX);
+        let client = ServiceClient::new(layer.layer(unavailable));
+        let raw = client
+            .get_raw("https://api.pinnacle.com/v1/odds")
+            .await
+            .unwrap();
+        assert_eq!(raw.status, 503);
+        assert_eq!(lock(&calls).len(), 8);
+    }
+}
@@ -0,0 +1,333 @@
+//! In-process test double of the API client, no HTTP involved.
+//!
+//! Responses are registered per request, per endpoint or as closures, failures are queued per
+//! endpoint, and every call is recorded for assertions. Responses for exact parameters take
+//! precedence, otherwise the latest registered response is used.
+//!
+//! ```rust
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = MockPinnacleClient::new();
+//! let balance: ClientBalanceResponse = serde_json::from_str(
+//!     r#"{"availableBalance":100,"outstandingTransactions":0,"givenCredit":0,"currency":"USD"}"#,
+//! )
+//! .unwrap();
+//! client.respond_any::<GetClientBalance>(&balance);
+//! client.fail_timeout(GetClientBalance::PATH);
+//!
+//! assert!(client.get(&GetClientBalance).await.unwrap_err().is_timeout());
+//! assert_eq!(client.get(&GetClientBalance).await?.currency, "USD");
+//! assert_eq!(client.calls().len(), 2);
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::canonical_url;
+use crate::client::{PinnacleClientError, RawResponse};
+use crate::traits::{request_url, PinnacleApiClient, PinnacleApiPostRequest, PinnacleApiRequest};
+use async_trait::async_trait;
+use reqwest::{IntoUrl, Method, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+type Responder = Arc<dyn Fn(&MockCall) -> Result<RawResponse, PinnacleClientError> + Send + Sync>;
+
+/// Mock API client
+#[derive(Default)]
+pub struct MockPinnacleClient {
+    state: Mutex<MockClientState>,
+}
+
+/// A call made to the [`MockPinnacleClient`]
+#[derive(Debug, Clone)]
+pub struct MockCall {
+    /// GET or POST
+    pub method: Method,
+    /// Full URL
+    pub url: Url,
+    /// Json body of a POST request
+    pub body: Option<Value>,
+}
+
+#[derive(Default)]
+struct MockClientState {
+    responders: Vec<(Route, Responder)>,
+    failures: HashMap<String, VecDeque<Failure>>,
+    calls: Vec<MockCall>,
+}
+
+/// Calls a responder is registered for
+struct Route {
+    method: Method,
+    path: String,
+    /// Canonical url if the response is only for certain parameters
+    url: Option<String>,
+}
+
+enum Failure {
+    Response(RawResponse),
+    Error(PinnacleClientError),
+    Timeout,
+}
+
+impl MockPinnacleClient {
+    /// Creates a client without any responses, requests fail with 404
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds to the GET request with exactly these parameters
+    pub fn respond<Q>(&self, query: &Q, response: &Q::Response)
+    where
+        Q: PinnacleApiRequest + Serialize,
+    {
+        let url = Url::parse(&request_url(query)).expect("valid request url");
+        let route = Route {
+            method: Method::GET,
+            path: Q::PATH.into(),
+            url: Some(canonical_url(&url)),
+        };
+        self.add(route, json_responder(response));
+    }
+
+    /// Responds to GET requests of the type whatever their parameters
+    pub fn respond_any<Q: PinnacleApiRequest>(&self, response: &Q::Response) {
+        let route = Route {
+            method: Method::GET,
+            path: Q::PATH.into(),
+            url: None,
+        };
+        self.add(route, json_responder(response));
+    }
+
+    /// Responds to POST requests of the type
+    pub fn respond_post<Q: PinnacleApiPostRequest>(&self, response: &Q::Response) {
+        let route = Route {
+            method: Method::POST,
+            path: Q::PATH.into(),
+            url: None,
+        };
+        self.add(route, json_responder(response));
+    }
+
+    /// Responds to requests of the method to the endpoint path, e.g. `/v1/odds`, with the
+    /// result of the closure
+    pub fn respond_with<F>(&self, method: Method, path: &str, f: F)
+    where
+        F: Fn(&MockCall) -> Result<RawResponse, PinnacleClientError> + Send + Sync + 'static,
+    {
+        let route = Route {
+            method,
+            path: path.into(),
+            url: None,
+        };
+        self.add(route, Arc::new(f));
+    }
+
+    /// Responds to the next request of the endpoint with the status and body, e.g. an API error
+    pub fn fail(&self, path: &str, status: u16, body: &str) {
+        let response = RawResponse {
+            status,
+            headers: Default::default(),
+            body: body.into(),
+        };
+        self.push_failure(path, Failure::Response(response));
+    }
+
+    /// Responds to the next request of the endpoint with an empty body
+    pub fn fail_empty(&self, path: &str) {
+        self.fail(path, 200, "");
+    }
+
+    /// Times out the next request of the endpoint
+    pub fn fail_timeout(&self, path: &str) {
+        self.push_failure(path, Failure::Timeout);
+    }
+
+    /// Fails the next request of the endpoint with the error
+    pub fn fail_with(&self, path: &str, error: PinnacleClientError) {
+        self.push_failure(path, Failure::Error(error));
+    }
+
+    /// Calls made so far
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.state().calls.clone()
+    }
+
+    /// Calls made so far to the endpoint path
+    pub fn calls_to(&self, path: &str) -> Vec<MockCall> {
+        let state = self.state();
+        let calls = state.calls.iter().filter(|call| call.url.path() == path);
+        calls.cloned().collect()
+    }
+
+    /// Forgets the calls made so far
+    pub fn clear_calls(&self) {
+        self.state().calls.clear();
+    }
+
+    fn add(&self, route: Route, responder: Responder) {
+        self.state().responders.push((route, responder));
+    }
+
+    fn push_failure(&self, path: &str, failure: Failure) {
+        let mut state = self.state();
+        state
+            .failures
+            .entry(path.into())
+            .or_default()
+            .push_back(failure);
+    }
+
+    /// Records the call and responds with the queued failure or the latest matching responder
+    fn call(&self, call: MockCall) -> Result<RawResponse, PinnacleClientError> {
+        let responder = {
+            let mut state = self.state();
+            state.calls.push(call.clone());
+            let failure = state
+                .failures
+                .get_mut(call.url.path())
+                .and_then(VecDeque::pop_front);
+            match failure {
+                Some(Failure::Response(response)) => return Ok(response),
+                Some(Failure::Error(e)) => return Err(e),
+                Some(Failure::Timeout) => return Err(PinnacleClientError::Timeout(call.url)),
+                None => (),
+            }
+            let url = canonical_url(&call.url);
+            let routes = || {
+                let routes = state.responders.iter().rev();
+                routes.filter(|(route, _)| {
+                    route.method == call.method && route.path == call.url.path()
+                })
+            };
+            let exact = routes().find(|(route, _)| route.url.as_ref() == Some(&url));
+            let responder = exact.or_else(|| routes().find(|(route, _)| route.url.is_none()));
+            responder.map(|(_, responder)| responder.clone())
+        };
+        match responder {
+            Some(responder) => responder(&call),
+            None => Ok(RawResponse {
+                status: 404,
+                headers: Default::default(),
+                body: format!("no mock response for {} {}", call.method, call.url),
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockClientState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for MockPinnacleClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state();
+        f.debug_struct("MockPinnacleClient")
+            .field("responders", &state.responders.len())
+            .field("calls", &state.calls)
+            .finish()
+    }
+}
+
+fn json_responder<T: Serialize>(response: &T) -> Responder {
+    let body = serde_json::to_string(response).expect("serializable response");
+    Arc::new(move |_| {
+        Ok(RawResponse {
+            status: 200,
+            headers: Default::default(),
+            body: body.clone(),
+        })
+    })
+}
+
+#[async_trait]
+impl PinnacleApiClient for MockPinnacleClient {
+    type Error = PinnacleClientError;
+
+    async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        self.get_raw(url.clone()).await?.parse(&url)
+    }
+
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        self.call(MockCall {
+            method: Method::GET,
+            url: url.into_url()?,
+            body: None,
+        })
+    }
+
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        let body = serde_json::to_value(body)
+            .map_err(|e| PinnacleClientError::EncodeJson(e, url.clone()))?;
+        self.call(MockCall {
+            method: Method::POST,
+            url: url.clone(),
+            body: Some(body),
+        })?
+        .parse(&url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::{GetLeagues, GetSports};
+    use crate::responses::{Leagues, SportsResponse};
+    use crate::util::parse_json;
+
+    #[tokio::test]
+    async fn test_mock_client() {
+        let client = MockPinnacleClient::new();
+        let sports: SportsResponse = parse_json(r#"{"sports":[]}"#).unwrap();
+        client.respond_any::<GetSports>(&sports);
+        let leagues: Leagues = parse_json(r#"{"leagues":[]}"#).unwrap();
+        client.respond(&GetLeagues { sport_id: 29 }, &leagues);
+        client.respond_with(Method::GET, GetLeagues::PATH, |call| {
+            Err(PinnacleClientError::Timeout(call.url.clone()))
+        });
+        client.fail_empty(GetSports::PATH);
+        client.fail(GetSports::PATH, 401, r#"{"code":"INVALID_CREDENTIALS"}"#);
+
+        assert!(matches!(
+            client.get(&GetSports).await,
+            Err(PinnacleClientError::EmptyJson(_))
+        ));
+        assert!(matches!(
+            client.get(&GetSports).await,
+            Err(PinnacleClientError::HttpStatus(401, _, _))
+        ));
+        assert!(client.get(&GetSports).await.unwrap().sports.is_empty());
+
+        // The closure is registered later, but the exact parameters take precedence
+        let leagues = client.get(&GetLeagues { sport_id: 29 }).await.unwrap();
+        assert!(leagues.leagues.is_empty());
+        let err = client.get(&GetLeagues { sport_id: 1 }).await.unwrap_err();
+        assert!(err.is_timeout());
+
+        assert!(matches!(
+            client.get_raw("https://api.pinnacle.com/v1/odds").await,
+            Ok(RawResponse { status: 404, .. })
+        ));
+        assert_eq!(client.calls().len(), 6);
+        assert_eq!(client.calls_to(GetLeagues::PATH).len(), 2);
+    }
+}
@@ -0,0 +1,511 @@
+//! Local stand-in for the Pinnacle API, so [`PinnacleClient`] and the code built on it can be
+//! tested end to end without hitting the real API.
+//!
+//! Responses are registered per endpoint path, programmatically or from a directory of json
+//! files. The server checks basic auth, answers `since` requests with the pushed updates,
+//! simulates rate limiting and error responses and accepts or rejects placed bets.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//! use serde_json::json;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let server = MockServer::start()?;
+//! server.require_auth("user", "password");
+//! server.set("/v1/odds", json!({"sportId": 29, "last": 0, "leagues": []}));
+//!
+//! let client = server.client("user", "password");
+//! let odds = client.get(&GetStraightOdds { sport_id: 29, ..Default::default() }).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::client::PinnacleClient;
+use crate::responses::{BetStatus, StraightBet};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use base64::Engine;
+use displaydoc::Display;
+use reqwest::Url;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{fs, io};
+use thiserror::Error;
+use tokio::sync::oneshot;
+
+const PLACE_BET_PATH: &str = "/v4/bets/straight";
+const BETS_PATH: &str = "/v3/bets";
+const LINE_PATH: &str = "/v2/line";
+
+/// Mock Pinnacle API server, running until dropped
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum MockServerError {
+    /// io
+    Io(#[from] io::Error),
+    /// fixture {1:?}
+    Fixture(#[source] serde_json::Error, PathBuf),
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    authorization: Option<String>,
+    version: i64,
+    endpoints: HashMap<String, Endpoint>,
+    failures: HashMap<String, VecDeque<(u16, String)>>,
+    rate_limited: usize,
+    bet_rejection: Option<String>,
+    bets: Vec<StraightBet>,
+    requests: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Endpoint {
+    snapshot: Option<Value>,
+    updates: Vec<(i64, Value)>,
+}
+
+impl MockServer {
+    /// Starts the server on a random local port, must be called within a Tokio runtime
+    pub fn start() -> Result<Self, MockServerError> {
+        Self::start_on(SocketAddr::from(([127, 0, 0, 1], 0)))
+    }
+
+    /// Starts the server on the address, must be called within a Tokio runtime
+    pub fn start_on(addr: SocketAddr) -> Result<Self, MockServerError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let app = Router::new().fallback(handle).with_state(state.clone());
+        let server = axum::Server::from_tcp(listener)
+            .map_err(io::Error::other)?
+            .serve(app.into_make_service());
+        let (shutdown, rx) = oneshot::channel();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            rx.await.ok();
+        }));
+        Ok(Self {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        })
+    }
+
+    /// Address the server listens on
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Origin to send the requests to, see [`PinnacleClient::with_origin`]
+    pub fn origin(&self) -> Url {
+        Url::parse(&format!("http://{}", self.addr)).expect("valid origin")
+    }
+
+    /// A client sending its requests to the server
+    pub fn client(&self, username: &str, password: &str) -> PinnacleClient {
+        PinnacleClient::new(username, password).with_origin(self.origin())
+    }
+
+    /// Rejects requests without these basic auth credentials with 401
+    pub fn require_auth(&self, username: &str, password: &str) {
+        let credentials =
+            base64::engine::general_purpose::STANDARD.encode(format!("{username}:{password}"));
+        self.state().authorization = Some(format!("Basic {credentials}"));
+    }
+
+    /// Sets the response of the endpoint to requests without `since`.
+    ///
+    /// A `last` field of the body is replaced with the next version, so that updates pushed
+    /// afterwards are returned to requests with `since` of this response.
+    pub fn set(&self, path: &str, mut body: Value) {
+        let mut state = self.state();
+        let version = state.next_version();
+        if let Some(last) = body.get_mut("last") {
+            *last = version.into();
+        }
+        state.endpoints.entry(path.into()).or_default().snapshot = Some(body);
+    }
+
+    /// Pushes an incremental update of the endpoint, returned to requests with an earlier
+    /// `since`. The `last` field of the body is set to the next version.
+    pub fn push_update(&self, path: &str, mut body: Value) {
+        let mut state = self.state();
+        let version = state.next_version();
+        if let Value::Object(map) = &mut body {
+            map.insert("last".into(), version.into());
+        }
+        let endpoint = state.endpoints.entry(path.into()).or_default();
+        endpoint.updates.push((version, body));
+    }
+
+    /// Sets the responses from json files of the directory, named after the endpoint paths,
+    /// e.g. `v1/odds.json` for `/v1/odds`
+    pub fn load_dir(&self, dir: impl AsRef<Path>) -> Result<(), MockServerError> {
+        let dir = dir.as_ref();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(current) = dirs.pop() {
+            for entry in fs::read_dir(&current)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+                if path.extension().is_none_or(|ext| ext != "json") {
+                    continue;
+                }
+                let json = fs::read_to_string(&path)?;
+                let body = serde_json::from_str(&json)
+                    .map_err(|e| MockServerError::Fixture(e, path.clone()))?;
+                let Ok(relative) = path
+                    .with_extension("")
+                    .strip_prefix(dir)
+                    .map(Path::to_owned)
+                else {
+                    continue;
+                };
+                let endpoint = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                self.set(&format!("/{endpoint}"), body);
+            }
+        }
+        Ok(())
+    }
+
+    /// Responds to the next request of the endpoint with the status and body
+    pub fn fail(&self, path: &str, status: u16, body: &str) {
+        self.state()
+            .failures
+            .entry(path.into())
+            .or_default()
+            .push_back((status, body.into()));
+    }
+
+    /// Responds to the next `times` requests with 429 Too Many Requests
+    pub fn rate_limit(&self, times: usize) {
+        self.state().rate_limited += times;
+    }
+
+    /// Rejects placed bets with the error code, e.g. `LINE_CHANGED`
+    pub fn reject_bets(&self, error_code: &str) {
+        self.state().bet_rejection = Some(error_code.into());
+    }
+
+    /// Accepts placed bets, the default
+    pub fn accept_bets(&self) {
+        self.state().bet_rejection = None;
+    }
+
+    /// The accepted bets
+    pub fn bets(&self) -> Vec<StraightBet> {
+        self.state().bets.clone()
+    }
+
+    /// Method and URI of every received request
+    pub fn requests(&self) -> Vec<String> {
+        self.state().requests.clone()
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().expect("mock server state")
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+impl MockState {
+    fn next_version(&mut self) -> i64 {
+        self.version += 1;
+        self.version
+    }
+
+    fn get(&self, path: &str, query: &HashMap<String, String>) -> Response {
+        if path == BETS_PATH {
+            return json_response(StatusCode::OK, self.find_bets(query).to_string());
+        }
+        let Some(endpoint) = self.endpoints.get(path) else {
+            return error_response(StatusCode::NOT_FOUND, "NOT_FOUND", "No response is set");
+        };
+        let since = query.get("since").and_then(|s| s.parse::<i64>().ok());
+        let body = match since {
+            Some(since) => endpoint
+                .updates
+                .iter()
+                .find(|(version, _)| *version > since)
+                .map(|(_, body)| body),
+            None => endpoint.snapshot.as_ref(),
+        };
+        match body {
+            Some(body) => json_response(StatusCode::OK, body.to_string()),
+            // The API responds with an empty body when nothing changed
+            None => json_response(StatusCode::OK, String::new()),
+        }
+    }
+
+    fn find_bets(&self, query: &HashMap<String, String>) -> Value {
+        let list = |key: &str| -> Option<Vec<String>> {
+            query
+                .get(key)
+                .map(|v| v.split(',').map(str::to_string).collect())
+        };
+        let bet_ids = list("betIds");
+        let request_ids = list("uniqueRequestIds");
+        let bets: Vec<_> = self
+            .bets
+            .iter()
+            .filter(|b| {
+                bet_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&b.bet_id.to_string()))
+            })
+            .filter(|b| {
+                request_ids.as_ref().is_none_or(|ids| {
+                    b.unique_request_id
+                        .as_ref()
+                        .is_some_and(|id| ids.contains(id))
+                })
+            })
+            .collect();
+        json!({ "straightBets": bets })
+    }
+
+    fn place_bet(&mut self, body: &[u8]) -> Response {
+        let bet: Value = match serde_json::from_slice(body) {
+            Ok(bet) => bet,
+            Err(e) => {
+                return error_response(StatusCode::BAD_REQUEST, "INVALID_REQUEST", &e.to_string())
+            }
+        };
+        let Some(request_id) = bet["uniqueRequestId"].as_str() else {
+            return error_response(
+                StatusCode::BAD_REQUEST,
+                "INVALID_REQUEST",
+                "uniqueRequestId is required",
+            );
+        };
+        // Resent bets are de-duplicated by their unique request id
+        if let Some(placed) = self
+            .bets
+            .iter()
+            .find(|b| b.unique_request_id.as_deref() == Some(request_id))
+        {
+            return placed_response(request_id, Some(placed));
+        }
+        if let Some(error_code) = &self.bet_rejection {
+            let body = json!({
+                "status": "PROCESSED_WITH_ERROR",
+                "errorCode": error_code,
+                "uniqueRequestId": request_id,
+            });
+            return json_response(StatusCode::OK, body.to_string());
+        }
+
+        let price = self
+            .endpoints
+            .get(LINE_PATH)
+            .and_then(|e| e.snapshot.as_ref())
+            .and_then(|line| line["price"].as_f64())
+            .unwrap_or(2.0);
+        let stake = bet["stake"].as_f64().unwrap_or_default();
+        let (risk, win) = if bet["winRiskStake"] == "WIN" {
+            (stake / (price - 1.0), stake)
+        } else {
+            (stake, stake * (price - 1.0))
+        };
+        let team_name = bet["team"].as_str().map(str::to_string);
+        let straight_bet = json!({
+            "betId": self.bets.len() + 1,
+            "uniqueRequestId": request_id,
+            "wagerNumber": 1,
+            "placedAt": chrono::Utc::now(),
+            "betStatus": BetStatus::Accepted,
+            "betType": bet["betType"],
+            "win": win,
+            "risk": risk,
+            "price": price,
+            "sportId": bet["sportId"],
+            "eventId": bet["eventId"],
+            "handicap": bet.get("handicap"),
+            "teamName": team_name,
+            "side": bet.get("side"),
+            "periodNumber": bet["periodNumber"],
+        });
+        match serde_json::from_value::<StraightBet>(straight_bet) {
+            Ok(placed) => {
+                self.bets.push(placed);
+                placed_response(request_id, self.bets.last())
+            }
+            Err(e) => error_response(StatusCode::BAD_REQUEST, "INVALID_REQUEST", &e.to_string()),
+        }
+    }
+}
+
+async fn handle(
+    State(state): State<Arc<Mutex<MockState>>>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut state = state.lock().expect("mock server state");
+    state.requests.push(format!("{method} {uri}"));
+
+    if let Some(expected) = &state.authorization {
+        let authorization = headers.get(header::AUTHORIZATION);
+        if authorization.and_then(|v| v.to_str().ok()) != Some(expected) {
+            return error_response(
+                StatusCode::UNAUTHORIZED,
+                "INVALID_CREDENTIALS",
+                "Invalid credentials",
+            );
+        }
+    }
+    if state.rate_limited > 0 {
+        state.rate_limited -= 1;
+        return error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            "TOO_MANY_REQUESTS",
+            "Too many requests",
+        );
+    }
+    let path = uri.path();
+    if let Some((status, body)) = state.failures.get_mut(path).and_then(|f| f.pop_front()) {
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        return json_response(status, body);
+    }
+
+    match (method, path) {
+        (Method::POST, PLACE_BET_PATH) => state.place_bet(&body),
+        (Method::GET, path) => {
+            let query =
+                serde_urlencoded::from_str(uri.query().unwrap_or_default()).unwrap_or_default();
+            state.get(path, &query)
+        }
+        _ => error_response(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "METHOD_NOT_ALLOWED",
+            "Method not allowed",
+        ),
+    }
+}
+
+fn placed_response(request_id: &str, bet: Option<&StraightBet>) -> Response {
+    let body = json!({
+        "status": "ACCEPTED",
+        "uniqueRequestId": request_id,
+        "straightBet": bet,
+    });
+    json_response(StatusCode::OK, body.to_string())
+}
+
+fn error_response(status: StatusCode, code: &str, message: &str) -> Response {
+    let body = json!({ "code": code, "message": message });
+    json_response(status, body.to_string())
+}
+
+fn json_response(status: StatusCode, body: String) -> Response {
+    (status, [(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[tokio::test]
+    async fn test_mock_server() {
+        let server = MockServer::start().unwrap();
+        server.require_auth("user", "password");
+        server.set("/v1/odds", json!({"sportId": 29, "last": 0, "leagues": []}));
+        server.push_update("/v1/odds", json!({"sportId": 29, "leagues": []}));
+        let client = server.client("user", "password");
+
+        let req = GetStraightOdds {
+            sport_id: 29,
+            ..Default::default()
+        };
+        let snapshot = client.get(&req).await.unwrap();
+        let req = GetStraightOdds {
+            since: Some(snapshot.last),
+            ..req
+        };
+        let update = client.get(&req).await.unwrap();
+        assert!(update.last > snapshot.last);
+        let req = GetStraightOdds {
+            since: Some(update.last),
+            ..req
+        };
+        assert!(matches!(
+            client.get(&req).await,
+            Err(PinnacleClientError::EmptyJson(_))
+        ));
+
+        let unauthorized = server.client("user", "wrong");
+        assert!(matches!(
+            unauthorized.get(&req).await,
+            Err(PinnacleClientError::HttpStatus(401, _, _))
+        ));
+        server.rate_limit(1);
+        assert!(matches!(
+            client.get(&req).await,
+            Err(PinnacleClientError::HttpStatus(429, _, _))
+        ));
+
+        let mut bet = PlaceStraightBet {
+            odds_format: OddsFormat::Decimal,
+            unique_request_id: "request-1".into(),
+            accept_better_line: true,
+            stake: 10.0,
+            win_risk_stake: WinRiskStake::Risk,
+            line_id: 1,
+            alt_line_id: None,
+            fill_type: FillType::Normal,
+            sport_id: 29,
+            event_id: 10,
+            period_number: 0,
+            bet_type: BetType::Moneyline,
+            team: Some(Team::Team1),
+            side: None,
+        };
+        let placed = client.post(&bet).await.unwrap();
+        assert_eq!(placed.status, PlaceBetStatus::Accepted);
+        assert_eq!(placed.straight_bet.unwrap().win, 10.0);
+        let bets = client
+            .get(&GetBets {
+                unique_request_ids: Some(vec!["request-1".into()]),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(bets.straight_bets.len(), 1);
+
+        server.reject_bets("LINE_CHANGED");
+        bet.unique_request_id = "request-2".into();
+        let rejected = client.post(&bet).await.unwrap();
+        assert_eq!(rejected.status, PlaceBetStatus::ProcessedWithError);
+        assert_eq!(rejected.error_code.as_deref(), Some("LINE_CHANGED"));
+        assert_eq!(server.bets().len(), 1);
+    }
+}
@@ -0,0 +1,438 @@
+//! Changes between consecutive odds snapshots: price moves, handicap and points moves, lines
+//! going offline, limit changes, markets added and removed.
+//!
+//! [`OddsDiff`] compares two full [`OddsResponse`]s, while [`OddsTracker`] keeps the state of a
+//! sport up to date with the `since` deltas and reports what each delta changed.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleClient::new("pinnacle_user", "pinnacle_password");
+//! let mut tracker = OddsTracker::new(DiffThresholds {
+//!     min_price_change: 0.05,
+//!     ..Default::default()
+//! });
+//! loop {
+//!     let req = GetStraightOdds {
+//!         sport_id: 29,
+//!         since: tracker.last(),
+//!         ..Default::default()
+//!     };
+//!     for change in tracker.update(client.get(&req).await?) {
+//!         println!("{change:?}");
+//!     }
+//! }
+//! # }
+//! ```
+use crate::market_book::{MarketType, Price, Side};
+use crate::responses::{OddsPeriod, OddsResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Minimal changes worth reporting, zeros report any change
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DiffThresholds {
+    /// Minimal price change
+    pub min_price_change: f64,
+    /// Minimal handicap or points change
+    pub min_line_change: f64,
+    /// Minimal maximum bet volume change
+    pub min_limit_change: f64,
+}
+
+/// Compares odds snapshots
+#[derive(Debug, Clone, Default)]
+pub struct OddsDiff {
+    thresholds: DiffThresholds,
+}
+
+/// A change of a period of an event
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OddsChange {
+    /// League id.
+    pub league_id: i32,
+    /// Event id.
+    pub event_id: i64,
+    /// Period number.
+    pub period_number: i32,
+    /// Time of the change from the `*_updated_at` fields of the market, if known.
+    pub at: Option<DateTime<Utc>>,
+    /// What has changed.
+    pub kind: ChangeKind,
+}
+
+/// What has changed
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub enum ChangeKind {
+    /// The price of a side moved while its line stayed the same
+    PriceMoved {
+        /// Market type.
+        market_type: MarketType,
+        /// Side of the market.
+        side: Side,
+        /// Handicap or points.
+        line: Option<f64>,
+        /// Alternative line id.
+        alt_line_id: Option<i64>,
+        /// Previous price.
+        from: f64,
+        /// New price.
+        to: f64,
+    },
+    /// The handicap or points of a side moved
+    LineMoved {
+        /// Market type.
+        market_type: MarketType,
+        /// Side of the market.
+        side: Side,
+        /// Alternative line id.
+        alt_line_id: Option<i64>,
+        /// Previous line.
+        from: Option<f64>,
+        /// New line.
+        to: Option<f64>,
+        /// Price at the new line.
+        price: f64,
+    },
+    /// The period went offline or online, 1 - online, 2 - offline
+    StatusChanged {
+        /// Previous status.
+        from: i32,
+        /// New status.
+        to: i32,
+    },
+    /// A maximum bet volume of the period changed
+    LimitChanged {
+        /// The limit.
+        limit: Limit,
+        /// Previous volume.
+        from: Option<f64>,
+        /// New volume.
+        to: Option<f64>,
+    },
+    /// A new price appeared
+    MarketAdded(Price),
+    /// A price disappeared
+    MarketRemoved(Price),
+}
+
+/// Maximum bet volume of a period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub enum Limit {
+    /// `maxMoneyline`
+    Moneyline,
+    /// `maxSpread`
+    Spread,
+    /// `maxTotal`
+    Total,
+    /// `maxTeamTotal`
+    TeamTotal,
+}
+
+/// Keeps the odds of a sport up to date with the `since` deltas
+#[derive(Debug, Default)]
+pub struct OddsTracker {
+    diff: OddsDiff,
+    last: Option<i64>,
+    periods: HashMap<(i64, i32), (i32, OddsPeriod)>,
+}
+
+type PriceKey = (MarketType, Side, Option<i64>);
+
+impl OddsDiff {
+    /// Creates a diff reporting changes above the thresholds
+    pub fn new(thresholds: DiffThresholds) -> Self {
+        Self { thresholds }
+    }
+
+    /// Changes between two full snapshots of the same sport
+    pub fn diff(&self, old: &OddsResponse, new: &OddsResponse) -> Vec<OddsChange> {
+        let mut old_periods: HashMap<(i64, i32), (i32, &OddsPeriod)> = HashMap::new();
+        for league in old.leagues.iter() {
+            for event in league.events.iter() {
+                for period in event.periods.iter() {
+                    old_periods.insert((event.id, period.number), (league.id, period));
+                }
+            }
+        }
+
+        let mut changes = Vec::new();
+        for league in new.leagues.iter() {
+            for event in league.events.iter() {
+                for period in event.periods.iter() {
+                    let old = old_periods.remove(&(event.id, period.number));
+                    let old = old.map(|(_, p)| p);
+                    self.diff_period(league.id, event.id, old, Some(period), &mut changes);
+                }
+            }
+        }
+        let mut removed: Vec<_> = old_periods.into_iter().collect();
+        removed.sort_by_key(|(key, _)| *key);
+        for ((event_id, _), (league_id, period)) in removed {
+            self.diff_period(league_id, event_id, Some(period), None, &mut changes);
+        }
+        changes
+    }
+
+    /// Changes of a period, a missing period has no prices
+    pub fn diff_period(
+        &self,
+        league_id: i32,
+        event_id: i64,
+        old: Option<&OddsPeriod>,
+        new: Option<&OddsPeriod>,
+        changes: &mut Vec<OddsChange>,
+    ) {
+        let Some(period_number) = new.or(old).map(|p| p.number) else {
+            return;
+        };
+        let mut push = |at, kind| {
+            changes.push(OddsChange {
+                league_id,
+                event_id,
+                period_number,
+                at,
+                kind,
+            })
+        };
+
+        if let (Some(old), Some(new)) = (old, new) {
+            if old.status != new.status {
+                let at = [
+                    new.moneyline_updated_at,
+                    new.spread_updated_at,
+                    new.total_updated_at,
+                    new.team_total_updated_at,
+                ]
+                .into_iter()
+                .max()
+                .flatten();
+                let (from, to) = (old.status, new.status);
+                push(at, ChangeKind::StatusChanged { from, to });
+            }
+            let limits = [
+                (Limit::Moneyline, old.max_moneyline, new.max_moneyline),
+                (Limit::Spread, old.max_spread, new.max_spread),
+                (Limit::Total, old.max_total, new.max_total),
+                (Limit::TeamTotal, old.max_team_total, new.max_team_total),
+            ];
+            for (limit, from, to) in limits {
+                if self.limit_changed(from, to) {
+                    let at = match limit {
+                        Limit::Moneyline => new.moneyline_updated_at,
+                        Limit::Spread => new.spread_updated_at,
+                        Limit::Total => new.total_updated_at,
+                        Limit::TeamTotal => new.team_total_updated_at,
+                    };
+                    push(at, ChangeKind::LimitChanged { limit, from, to });
+                }
+            }
+        }
+
+        let old_prices = old.map(OddsPeriod::prices).unwrap_or_default();
+        let new_prices = new.map(OddsPeriod::prices).unwrap_or_default();
+        let mut old_prices: HashMap<PriceKey, Price> =
+            old_prices.into_iter().map(|p| (price_key(&p), p)).collect();
+        for price in new_prices.iter() {
+            let Some(old) = old_prices.remove(&price_key(price)) else {
+                push(price.updated_at, ChangeKind::MarketAdded(price.clone()));
+                continue;
+            };
+            let (market_type, side, alt_line_id) = price_key(price);
+            if self.line_changed(old.line, price.line) {
+                let kind = ChangeKind::LineMoved {
+                    market_type,
+                    side,
+                    alt_line_id,
+                    from: old.line,
+                    to: price.line,
+                    price: price.price,
+                };
+                push(price.updated_at, kind);
+            } else if changed(old.price, price.price, self.thresholds.min_price_change) {
+                let kind = ChangeKind::PriceMoved {
+                    market_type,
+                    side,
+                    line: price.line,
+                    alt_line_id,
+                    from: old.price,
+                    to: price.price,
+                };
+                push(price.updated_at, kind);
+            }
+        }
+        let mut removed: Vec<_> = old_prices.into_values().collect();
+        removed.sort_by_key(price_key);
+        for price in removed {
+            push(price.updated_at, ChangeKind::MarketRemoved(price));
+        }
+    }
+
+    fn line_changed(&self, from: Option<f64>, to: Option<f64>) -> bool {
+        match (from, to) {
+            (Some(from), Some(to)) => changed(from, to, self.thresholds.min_line_change),
+            (from, to) => from.is_some() != to.is_some(),
+        }
+    }
+
+    fn limit_changed(&self, from: Option<f64>, to: Option<f64>) -> bool {
+        match (from, to) {
+            (Some(from), Some(to)) => changed(from, to, self.thresholds.min_limit_change),
+            (from, to) => from.is_some() != to.is_some(),
+        }
+    }
+}
+
+impl OddsTracker {
+    /// Creates an empty tracker reporting changes above the thresholds
+    pub fn new(thresholds: DiffThresholds) -> Self {
+        Self {
+            diff: OddsDiff::new(thresholds),
+            ..Default::default()
+        }
+    }
+
+    /// The `since` value for the next request, `None` until the first update
+    pub fn last(&self) -> Option<i64> {
+        self.last
+    }
+
+    /// The current state of a period
+    pub fn period(&self, event_id: i64, number: i32) -> Option<&OddsPeriod> {
+        self.periods.get(&(event_id, number)).map(|(_, p)| p)
+    }
+
+    /// The current periods along with their league and event ids
+    pub fn periods(&self) -> impl Iterator<Item = (i32, i64, &OddsPeriod)> {
+        self.periods
+            .iter()
+            .map(|((event_id, _), (league_id, period))| (*league_id, *event_id, period))
+    }
+
+    /// Merges a full snapshot or a `since` delta, returning the changes. A delta contains only
+    /// the changed periods, each of them complete, so the periods it lacks stay as they are.
+    pub fn update(&mut self, odds: OddsResponse) -> Vec<OddsChange> {
+        let mut changes = Vec::new();
+        for league in odds.leagues {
+            for event in league.events {
+                for period in event.periods {
+                    let key = (event.id, period.number);
+                    let old = self.periods.get(&key).map(|(_, p)| p);
+                    self.diff
+                        .diff_period(league.id, event.id, old, Some(&period), &mut changes);
+                    self.periods.insert(key, (league.id, period));
+                }
+            }
+        }
+        self.last = Some(odds.last);
+        changes
+    }
+
+    /// Forgets the periods with a wagering cut-off before `time`, returns their number
+    pub fn remove_cut_off(&mut self, time: DateTime<Utc>) -> usize {
+        let before = self.periods.len();
+        self.periods.retain(|_, (_, p)| p.cutoff >= time);
+        before - self.periods.len()
+    }
+}
+
+fn price_key(price: &Price) -> PriceKey {
+    (price.market_type, price.side, price.alt_line_id)
+}
+
+fn changed(from: f64, to: f64, threshold: f64) -> bool {
+    let delta = (to - from).abs();
+    delta > 0.0 && delta >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_json;
+
+    fn odds(last: i64, period: &str) -> OddsResponse {
+        parse_json(&format!(
+            r#"{{"sportId": 29, "last": {last}, "leagues": [{{"id": 1, "events": [
+                {{"id": 10, "periods": [{period}]}}]}}]}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_odds_diff() {
+        let old = odds(
+            1,
+            r#"{"lineId": 100, "number": 0, "cutoff": "2023-04-16T18:00:00Z", "status": 1,
+                "maxSpread": 500, "moneyline": {"home": 2.1, "away": 3.5},
+                "spreads": [{"hdp": -0.5, "home": 2.0, "away": 1.9}],
+                "totals": [{"points": 2.5, "over": 1.9, "under": 1.95}]}"#,
+        );
+        let new = odds(
+            2,
+            r#"{"lineId": 101, "number": 0, "cutoff": "2023-04-16T18:00:00Z", "status": 2,
+                "maxSpread": 250, "moneyline": {"home": 2.12, "away": 3.3},
+                "spreads": [{"hdp": -0.75, "home": 2.05, "away": 1.85}],
+                "spreadUpdatedAt": "2023-04-16T17:00:00Z"}"#,
+        );
+        let diff = OddsDiff::new(DiffThresholds {
+            min_price_change: 0.05,
+            ..Default::default()
+        });
+        let changes = diff.diff(&old, &new);
+        let kinds: Vec<_> = changes.iter().map(|c| &c.kind).collect();
+        assert!(matches!(
+            kinds[..],
+            [
+                ChangeKind::StatusChanged { from: 1, to: 2 },
+                ChangeKind::LimitChanged {
+                    limit: Limit::Spread,
+                    from: Some(500.0),
+                    to: Some(250.0)
+                },
+                ChangeKind::PriceMoved {
+                    market_type: MarketType::Moneyline,
+                    side: Side::Away,
+                    ..
+                },
+                ChangeKind::LineMoved {
+                    side: Side::Home,
+                    from: Some(-0.5),
+                    to: Some(-0.75),
+                    ..
+                },
+                ChangeKind::LineMoved {
+                    side: Side::Away,
+                    ..
+                },
+                ChangeKind::MarketRemoved(Price {
+                    side: Side::Over,
+                    ..
+                }),
+                ChangeKind::MarketRemoved(Price {
+                    side: Side::Under,
+                    ..
+                }),
+            ]
+        ));
+        assert_eq!(
+            changes[3].at,
+            new.leagues[0].events[0].periods[0].spread_updated_at
+        );
+
+        let mut tracker = OddsTracker::default();
+        assert!(tracker
+            .update(old)
+            .iter()
+            .all(|c| matches!(c.kind, ChangeKind::MarketAdded(_))));
+        assert_eq!(tracker.update(new).len(), 8);
+        assert_eq!(tracker.last(), Some(2));
+        assert!(
+            tracker.update(odds(3, "")).is_empty(),
+            "empty delta changes nothing"
+        );
+        assert_eq!(tracker.period(10, 0).unwrap().line_id, 101);
+    }
+}
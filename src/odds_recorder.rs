@@ -0,0 +1,429 @@
+//! Historical odds recording for backtests.
+//!
+//! [`OddsRecorder`] consumes odds snapshots or `since` deltas and appends a [`Tick`] for every
+//! selection which price, line or limit changed to a [`TickStore`]. The store is a folder of
+//! append-only gzipped NDJSON segments along with an `index.json` telling the time range and the
+//! events of each segment, so queries only decompress the segments they need. The index is checked
+//! against the segment files when the store is opened and the segments it's missing or which grew
+//! since are scanned again, so a crash between an append and the index update loses no tick.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = PinnacleClient::new("pinnacle_user", "pinnacle_password");
+//! let mut recorder = OddsRecorder::new(TickStore::open("ticks")?)?;
+//! let mut last = None;
+//! loop {
+//!     let req = GetStraightOdds {
+//!         sport_id: 29,
+//!         since: last,
+//!         ..Default::default()
+//!     };
+//!     let odds = client.get(&req).await?;
+//!     last = Some(odds.last);
+//!     recorder.record(&odds)?;
+//!     # break;
+//! }
+//! let closing = recorder.store().closing_line(1578036305, 0)?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::market_book::{MarketType, Side};
+use crate::responses::OddsResponse;
+use crate::util::parse_json;
+use chrono::{DateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+const INDEX_FILE: &str = "index.json";
+
+/// A price of a selection at a point in time
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Tick {
+    /// Event id.
+    pub event_id: i64,
+    /// Period number.
+    pub period_number: i32,
+    /// Market type.
+    pub market_type: MarketType,
+    /// Side of the market.
+    pub side: Side,
+    /// Alternative line id.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub alt_line_id: Option<i64>,
+    /// Handicap or points.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub line: Option<f64>,
+    /// The price.
+    pub price: f64,
+    /// Maximum bet volume.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max: Option<f64>,
+    /// Period’s wagering cut-off at the time of the tick.
+    pub cutoff: DateTime<Utc>,
+    /// Time of the market update, or of the recording if Pinnacle didn't tell.
+    pub at: DateTime<Utc>,
+}
+
+/// Append-only compressed storage of ticks
+#[derive(Debug)]
+pub struct TickStore {
+    dir: PathBuf,
+    index: StoreIndex,
+    max_segment_size: u64,
+}
+
+/// Ticks of selections which changed since the previous snapshots
+#[derive(Debug)]
+pub struct OddsRecorder {
+    store: TickStore,
+    last: HashMap<SelectionKey, Tick>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct StoreIndex {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Segment {
+    file: String,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    ticks: usize,
+    events: BTreeSet<i64>,
+    #[serde(default)]
+    bytes: u64,
+}
+
+type SelectionKey = (i64, i32, MarketType, Side, Option<i64>);
+
+impl TickStore {
+    /// Opens the store in the `dir` folder, creating it if necessary
+    ///
+    /// Segments missing from the index or longer than it tells are scanned to bring it up to date.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let index = match fs::read_to_string(dir.join(INDEX_FILE)) {
+            Ok(s) => parse_json(&s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => StoreIndex::default(),
+            Err(e) => return Err(e),
+        };
+        let mut store = Self {
+            dir,
+            index,
+            max_segment_size: 64 * 1024 * 1024,
+        };
+        store.recover()?;
+        Ok(store)
+    }
+
+    /// Sets the size of a segment file after which a new one is started
+    pub fn with_max_segment_size(mut self, bytes: u64) -> Self {
+        self.max_segment_size = bytes;
+        self
+    }
+
+    /// Appends the ticks as a new gzip member of the last segment
+    pub fn append(&mut self, ticks: &[Tick]) -> io::Result<()> {
+        let (Some(from), Some(to)) = (
+            ticks.iter().map(|t| t.at).min(),
+            ticks.iter().map(|t| t.at).max(),
+        ) else {
+            return Ok(());
+        };
+        let segment_full = match self.index.segments.last() {
+            Some(segment) => fs::metadata(self.dir.join(&segment.file))
+                .map(|m| m.len() >= self.max_segment_size)
+                .unwrap_or(true),
+            None => true,
+        };
+        if segment_full {
+            self.index.segments.push(Segment {
+                file: format!("ticks-{:06}.ndjson.gz", self.index.segments.len()),
+                from,
+                to,
+                ticks: 0,
+                events: BTreeSet::new(),
+                bytes: 0,
+            });
+        }
+        let Some(segment) = self.index.segments.last_mut() else {
+            return Ok(());
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(&segment.file))?;
+        let mut gz = GzEncoder::new(file, Compression::default());
+        for tick in ticks {
+            serde_json::to_writer(&mut gz, tick)?;
+            gz.write_all(b"\n")?;
+        }
+        let file = gz.finish()?;
+        file.sync_data()?;
+
+        segment.bytes = file.metadata()?.len();
+        segment.from = segment.from.min(from);
+        segment.to = segment.to.max(to);
+        segment.ticks += ticks.len();
+        segment.events.extend(ticks.iter().map(|t| t.event_id));
+        self.write_index()
+    }
+
+    /// All the ticks of an event, in the recording order
+    pub fn event_ticks(&self, event_id: i64) -> io::Result<Vec<Tick>> {
+        self.read(|s| s.events.contains(&event_id), |t| t.event_id == event_id)
+    }
+
+    /// Ticks with the time in the `from..to` range, in the recording order
+    pub fn range(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> io::Result<Vec<Tick>> {
+        self.read(
+            |s| s.from < to && s.to >= from,
+            |t| t.at >= from && t.at < to,
+        )
+    }
+
+    /// Closing line of a period: the last tick of each selection at or before the cut-off
+    pub fn closing_line(&self, event_id: i64, period_number: i32) -> io::Result<Vec<Tick>> {
+        let mut ticks = self.event_ticks(event_id)?;
+        ticks.retain(|t| t.period_number == period_number);
+        Ok(closing_line(&ticks))
+    }
+
+    /// Total number of ticks
+    pub fn len(&self) -> usize {
+        self.index.segments.iter().map(|s| s.ticks).sum()
+    }
+
+    /// Whether there are no ticks
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read(
+        &self,
+        segment_filter: impl Fn(&Segment) -> bool,
+        tick_filter: impl Fn(&Tick) -> bool,
+    ) -> io::Result<Vec<Tick>> {
+        let mut ticks = Vec::new();
+        for segment in self.index.segments.iter().filter(|s| segment_filter(s)) {
+            ticks.extend(
+                self.segment_ticks(&segment.file)?
+                    .into_iter()
+                    .filter(&tick_filter),
+            );
+        }
+        Ok(ticks)
+    }
+
+    fn segment_ticks(&self, file: &str) -> io::Result<Vec<Tick>> {
+        let file = File::open(self.dir.join(file))?;
+        BufReader::new(MultiGzDecoder::new(file))
+            .lines()
+            .map(|line| {
+                parse_json(&line?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            })
+            .collect()
+    }
+
+    /// Rebuilds the index entries of the segments written after the last index update
+    fn recover(&mut self) -> io::Result<()> {
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name.starts_with("ticks-") && name.ends_with(".ndjson.gz") {
+                files.push(name);
+            }
+        }
+        files.sort();
+
+        let mut changed = false;
+        for (i, file) in files.into_iter().enumerate() {
+            let bytes = fs::metadata(self.dir.join(&file))?.len();
+            let indexed = self.index.segments.get(i);
+            if indexed.is_some_and(|s| s.file == file && s.bytes == bytes) {
+                continue;
+            }
+            let ticks = self.segment_ticks(&file)?;
+            let (Some(from), Some(to)) = (
+                ticks.iter().map(|t| t.at).min(),
+                ticks.iter().map(|t| t.at).max(),
+            ) else {
+                continue;
+            };
+            let segment = Segment {
+                file,
+                from,
+                to,
+                ticks: ticks.len(),
+                events: ticks.iter().map(|t| t.event_id).collect(),
+                bytes,
+            };
+            self.index.segments.truncate(i);
+            self.index.segments.push(segment);
+            changed = true;
+        }
+        if changed {
+            self.write_index()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the index atomically, so a crash can't leave it half written
+    fn write_index(&self) -> io::Result<()> {
+        let path = self.dir.join(INDEX_FILE);
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_string(&self.index)?)?;
+        fs::rename(tmp, path)
+    }
+}
+
+impl OddsRecorder {
+    /// Records into the store, comparing the first snapshot with the latest recorded ticks
+    pub fn new(store: TickStore) -> io::Result<Self> {
+        let mut last = HashMap::new();
+        for tick in store.read(|_| true, |_| true)? {
+            last.insert(selection_key(&tick), tick);
+        }
+        Ok(Self { store, last })
+    }
+
+    /// The store
+    pub fn store(&self) -> &TickStore {
+        &self.store
+    }
+
+    /// Appends ticks of the selections which changed, returns the number of the ticks
+    pub fn record(&mut self, odds: &OddsResponse) -> io::Result<usize> {
+        let now = Utc::now();
+        let mut ticks = Vec::new();
+        for league in odds.leagues.iter() {
+            for event in league.events.iter() {
+                for period in event.periods.iter() {
+                    for price in period.prices() {
+                        let tick = Tick {
+                            event_id: event.id,
+                            period_number: period.number,
+                            market_type: price.market_type,
+                            side: price.side,
+                            alt_line_id: price.alt_line_id,
+                            line: price.line,
+                            price: price.price,
+                            max: price.max,
+                            cutoff: period.cutoff,
+                            at: price.updated_at.unwrap_or(now),
+                        };
+                        let key = selection_key(&tick);
+                        let unchanged = self.last.get(&key).is_some_and(|last| {
+                            (last.line, last.price, last.max, last.cutoff)
+                                == (tick.line, tick.price, tick.max, tick.cutoff)
+                        });
+                        if !unchanged {
+                            self.last.insert(key, tick.clone());
+                            ticks.push(tick);
+                        }
+                    }
+                }
+            }
+        }
+        self.store.append(&ticks)?;
+        Ok(ticks.len())
+    }
+}
+
+/// The last tick of each selection at or before its cut-off, taken from the latest tick
+pub fn closing_line(ticks: &[Tick]) -> Vec<Tick> {
+    let Some(cutoff) = ticks.iter().max_by_key(|t| t.at).map(|t| t.cutoff) else {
+        return Vec::new();
+    };
+    let mut closing: HashMap<SelectionKey, &Tick> = HashMap::new();
+    for tick in ticks.iter().filter(|t| t.at <= cutoff) {
+        let latest = closing.entry(selection_key(tick)).or_insert(tick);
+        if tick.at >= latest.at {
+            *latest = tick;
+        }
+    }
+    let mut closing: Vec<Tick> = closing.into_values().cloned().collect();
+    closing.sort_by_key(|t| (t.period_number, t.market_type, t.side, t.alt_line_id));
+    closing
+}
+
+fn selection_key(tick: &Tick) -> SelectionKey {
+    (
+        tick.event_id,
+        tick.period_number,
+        tick.market_type,
+        tick.side,
+        tick.alt_line_id,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn odds(moneyline: &str, updated_at: &str) -> OddsResponse {
+        parse_json(&format!(
+            r#"{{"sportId": 29, "last": 1, "leagues": [{{"id": 1, "events": [{{"id": 10,
+                "periods": [{{"lineId": 100, "number": 0, "cutoff": "2023-04-16T18:00:00Z",
+                "status": 1, "moneylineUpdatedAt": "{updated_at}",
+                "moneyline": {moneyline}}}]}}]}}]}}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_odds_recorder() {
+        let dir = std::env::temp_dir().join(format!("pinnacle-ticks-{}", std::process::id()));
+        let store = TickStore::open(&dir).unwrap().with_max_segment_size(1);
+        let mut recorder = OddsRecorder::new(store).unwrap();
+
+        let before = odds(r#"{"home": 2.1, "away": 3.5}"#, "2023-04-16T17:00:00Z");
+        assert_eq!(recorder.record(&before).unwrap(), 2);
+        assert_eq!(recorder.record(&before).unwrap(), 0, "nothing changed");
+        let closing = odds(r#"{"home": 2.0, "away": 3.5}"#, "2023-04-16T17:59:00Z");
+        assert_eq!(recorder.record(&closing).unwrap(), 1);
+        let live = odds(r#"{"home": 1.5, "away": 5.0}"#, "2023-04-16T18:30:00Z");
+        assert_eq!(recorder.record(&live).unwrap(), 2);
+
+        let store = TickStore::open(&dir).unwrap();
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.index.segments.len(), 3);
+        assert_eq!(store.event_ticks(10).unwrap().len(), 5);
+        assert!(store.event_ticks(11).unwrap().is_empty());
+        let from = "2023-04-16T17:30:00Z".parse().unwrap();
+        let to = "2023-04-16T18:00:00Z".parse().unwrap();
+        assert_eq!(store.range(from, to).unwrap().len(), 1);
+
+        let prices: Vec<_> = store
+            .closing_line(10, 0)
+            .unwrap()
+            .iter()
+            .map(|t| (t.side, t.price))
+            .collect();
+        assert_eq!(prices, vec![(Side::Home, 2.0), (Side::Away, 3.5)]);
+
+        // a restarted recorder knows the recorded prices
+        let mut recorder = OddsRecorder::new(store).unwrap();
+        assert_eq!(recorder.record(&live).unwrap(), 0);
+
+        // ticks appended before a crash which left the index behind are still found
+        fs::write(dir.join(INDEX_FILE), r#"{"segments": []}"#).unwrap();
+        let store = TickStore::open(&dir).unwrap();
+        assert_eq!(store.len(), 5);
+        assert_eq!(store.event_ticks(10).unwrap().len(), 5);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
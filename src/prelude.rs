@@ -1,6 +1,28 @@
 //! Structs and traits for convenient import
+#[cfg(feature = "arrow")]
+pub use crate::arrow_export::*;
+pub use crate::bet_guard::*;
+pub use crate::bet_submitter::*;
+#[cfg(feature = "blocking")]
+pub use crate::blocking_client::*;
+pub use crate::cache_store::*;
 pub use crate::caching_client::*;
 pub use crate::client::*;
+pub use crate::export::*;
+pub use crate::market_book::*;
+#[cfg(feature = "tower")]
+pub use crate::middleware::*;
+pub use crate::mock_client::*;
+#[cfg(feature = "mock-server")]
+pub use crate::mock_server::*;
+pub use crate::odds_diff::*;
+pub use crate::odds_recorder::*;
+pub use crate::replay_client::*;
 pub use crate::requests::*;
 pub use crate::responses::*;
+pub use crate::schema_check::*;
+pub use crate::selection::*;
+pub use crate::single_flight::*;
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite_cache_store::*;
 pub use crate::traits::*;
@@ -0,0 +1,209 @@
+//! Pinnacle API client recording responses into cassette files and replaying them, so tests can
+//! run offline and without credentials.
+//!
+//! Record the cassettes once with real credentials:
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleReplayClient::new(
+//!     "pinnacle_user",
+//!     "pinnacle_password",
+//!     "tests/cassettes",
+//!     ReplayMode::RecordMissing,
+//! );
+//! let sports = client.get(&GetSports).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! And replay them in CI, where any request without a cassette fails:
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleReplayClient::replay("tests/cassettes");
+//! let sports = client.get(&GetSports).await?;
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::{cache_key, canonical_url};
+use crate::client::{PinnacleClient, PinnacleClientError, RawResponse};
+use crate::traits::PinnacleApiClient;
+use crate::util::parse_json;
+use async_trait::async_trait;
+use reqwest::{IntoUrl, Url};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::marker::Send;
+use std::path::{Path, PathBuf};
+
+/// Pinnacle API client
+#[derive(Debug)]
+pub struct PinnacleReplayClient {
+    client: PinnacleClient,
+    dir: PathBuf,
+    mode: ReplayMode,
+}
+
+/// Where the responses come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayMode {
+    /// Always call the API, overwriting the cassettes
+    Record,
+    /// Only use the cassettes, a request without a cassette fails with
+    /// [`PinnacleClientError::CassetteNotFound`]
+    Replay,
+    /// Use the cassettes, calling the API and recording the responses which aren't recorded yet
+    RecordMissing,
+}
+
+/// A recorded request along with its response
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Cassette {
+    /// Canonical request url
+    pub url: String,
+    /// The response
+    pub response: RawResponse,
+}
+
+impl PinnacleReplayClient {
+    /// Creates a new client keeping cassettes in the `dir` folder
+    pub fn new(
+        username: impl Into<String>,
+        password: impl Into<String>,
+        dir: impl Into<PathBuf>,
+        mode: ReplayMode,
+    ) -> Self {
+        Self {
+            client: PinnacleClient::new(username, password),
+            dir: dir.into(),
+            mode,
+        }
+    }
+
+    /// Creates a client in [`ReplayMode::Replay`], which doesn't need credentials
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self::new("", "", dir, ReplayMode::Replay)
+    }
+
+    fn cassette_path(&self, url: &Url) -> PathBuf {
+        self.dir.join(format!("{}.json", cache_key(url)))
+    }
+}
+
+#[async_trait]
+impl PinnacleApiClient for PinnacleReplayClient {
+    type Error = PinnacleClientError;
+
+    async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url()?;
+        self.get_raw(url.clone()).await?.parse(&url)
+    }
+
+    /// Returns the response from the cassette or from the API depending on the mode
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        let url = url.into_url()?;
+        let path = self.cassette_path(&url);
+        if self.mode != ReplayMode::Record {
+            if let Some(cassette) = read_cassette(&path, &url)? {
+                return Ok(cassette.response);
+            }
+            if self.mode == ReplayMode::Replay {
+                return Err(PinnacleClientError::CassetteNotFound(url));
+            }
+        }
+        let response = self.client.get_raw(url.clone()).await?;
+        let cassette = Cassette {
+            url: canonical_url(&url),
+            response,
+        };
+        write_cassette(&path, &cassette)?;
+        Ok(cassette.response)
+    }
+
+    /// POST requests aren't recorded, they're always sent to the API
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        self.client.post_by_url(url, body).await
+    }
+}
+
+fn read_cassette(path: &Path, url: &Url) -> Result<Option<Cassette>, PinnacleClientError> {
+    let content = match fs::read_to_string(path) {
+        Ok(s) => s,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(PinnacleClientError::Cassette(e, path.into())),
+    };
+    parse_json(&content)
+        .map(Some)
+        .map_err(|e| PinnacleClientError::DecodeJson(e, url.clone()))
+}
+
+fn write_cassette(path: &Path, cassette: &Cassette) -> Result<(), PinnacleClientError> {
+    let err = |e| PinnacleClientError::Cassette(e, path.into());
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(err)?;
+    }
+    let content = serde_json::to_string_pretty(cassette).map_err(|e| err(e.into()))?;
+    fs::write(path, content).map_err(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::GetSports;
+    use crate::traits::request_url;
+
+    #[tokio::test]
+    async fn test_replay() {
+        let dir = std::env::temp_dir().join(format!("pinnacle-replay-{}", std::process::id()));
+        let client = PinnacleReplayClient::replay(&dir);
+        let url = Url::parse(&request_url(&GetSports)).unwrap();
+
+        let err = client.get(&GetSports).await.unwrap_err();
+        assert!(matches!(err, PinnacleClientError::CassetteNotFound(_)));
+
+        let sports = r#"{"sports": [{"id": 29, "name": "Soccer", "hasOfferings": true,
+            "leagueSpecialsCount": 0, "eventSpecialsCount": 0, "eventCount": 1}]}"#;
+        let ok = RawResponse {
+            status: 200,
+            headers: Default::default(),
+            body: sports.into(),
+        };
+        let cassette = |response| Cassette {
+            url: canonical_url(&url),
+            response,
+        };
+        write_cassette(&client.cassette_path(&url), &cassette(ok)).unwrap();
+        let resp = client.get(&GetSports).await.unwrap();
+        assert_eq!(resp.sports[0].name, "Soccer");
+
+        let unauthorized = RawResponse {
+            status: 401,
+            headers: Default::default(),
+            body: "".into(),
+        };
+        write_cassette(&client.cassette_path(&url), &cassette(unauthorized)).unwrap();
+        let err = client.get(&GetSports).await.unwrap_err();
+        assert!(matches!(err, PinnacleClientError::HttpStatus(401, _, _)));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
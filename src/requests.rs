@@ -1,8 +1,8 @@
 //! Typed Pinnacle API requests
 use crate::{
     responses::*,
-    traits::PinnacleApiRequest,
-    util::{serialize_bool_1_or_skip, serialize_comma_separated_option},
+    traits::{PinnacleApiPostRequest, PinnacleApiRequest},
+    util::{serialize_bool_1_or_skip, serialize_comma_separated_option, serialize_uppercase},
 };
 use serde::{Deserialize, Serialize};
 
@@ -53,17 +53,22 @@ impl PinnacleApiRequest for GetPeriods {
 }
 
 /// Format to request the odds.
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
 pub enum OddsFormat {
     /// American
+    #[serde(alias = "AMERICAN")]
     American,
     /// Decimal
+    #[serde(alias = "DECIMAL")]
     Decimal,
     /// HongKong
+    #[serde(alias = "HONGKONG")]
     HongKong,
     /// Indonesian
+    #[serde(alias = "INDONESIAN")]
     Indonesian,
     /// Malay
+    #[serde(alias = "MALAY")]
     Malay,
 }
 
@@ -137,6 +142,176 @@ impl PinnacleApiRequest for GetFixtures {
     type Response = FixturesResponse;
 }
 
+/// Returns all fixtures settled in the last 24 hours for the given sport.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetSettledFixtures {
+    /// The ID of the sport to retrieve the settled fixtures for.
+    pub sport_id: i32,
+    /// An optional list of league IDs to filter the fixtures by.
+    #[serde(serialize_with = "serialize_comma_separated_option")]
+    pub league_ids: Option<Vec<i32>>,
+    /// An optional timestamp to receive incremental updates, the value of `last` from the
+    /// previous response.
+    pub since: Option<i64>,
+}
+
+impl PinnacleApiRequest for GetSettledFixtures {
+    const PATH: &'static str = "/v3/fixtures/settled";
+    type Response = SettledFixturesResponse;
+}
+
+/// Places a straight bet. Pinnacle de-duplicates bets by `unique_request_id`, so resending a bet
+/// with the same id doesn't place it twice.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceStraightBet {
+    /// Format of the odds in the response.
+    #[serde(serialize_with = "serialize_uppercase")]
+    pub odds_format: OddsFormat,
+    /// Unique id of the request, a GUID. Used to identify duplicate requests, it's valid for 30
+    /// minutes.
+    pub unique_request_id: String,
+    /// Whether or not to accept a bet when there is a line change in favor of the client.
+    pub accept_better_line: bool,
+    /// Wagered amount in client's currency.
+    pub stake: f64,
+    /// Whether the stake amount is risk or win amount.
+    pub win_risk_stake: WinRiskStake,
+    /// Line identification, as in the odds and the line responses.
+    pub line_id: i64,
+    /// Alternate line identification.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alt_line_id: Option<i64>,
+    /// How to handle the stake exceeding the limit.
+    pub fill_type: FillType,
+    /// Sport identification.
+    pub sport_id: i32,
+    /// Event identification.
+    pub event_id: i64,
+    /// Period of the match that is being bet on, e.g. 0 for the match, 1 for the 1st half.
+    pub period_number: i32,
+    /// Type of the bet.
+    pub bet_type: BetType,
+    /// Chosen team, for moneyline, spread and team total bets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub team: Option<Team>,
+    /// Chosen side, for total and team total bets.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<BetSide>,
+}
+
+impl PinnacleApiPostRequest for PlaceStraightBet {
+    const PATH: &'static str = "/v4/bets/straight";
+    type Response = PlaceBetResponse;
+}
+
+/// Returns bets by their ids or unique request ids, e.g. to find out what happened to a bet
+/// whose placement request timed out.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetBets {
+    /// An optional list of bet ids.
+    #[serde(serialize_with = "serialize_comma_separated_option")]
+    pub bet_ids: Option<Vec<i64>>,
+    /// An optional list of unique request ids the bets were placed with, valid for 30 minutes.
+    #[serde(serialize_with = "serialize_comma_separated_option")]
+    pub unique_request_ids: Option<Vec<String>>,
+}
+
+impl PinnacleApiRequest for GetBets {
+    const PATH: &'static str = "/v3/bets";
+    type Response = BetsResponse;
+}
+
+/// Returns the latest line of a selection, which is needed to place a bet at the current price.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetLine {
+    /// League identification.
+    pub league_id: i32,
+    /// Handicap of the chosen team or side, needed for spread, total and team total bets.
+    pub handicap: Option<f64>,
+    /// Format of the odds in the response.
+    pub odds_format: OddsFormat,
+    /// Sport identification.
+    pub sport_id: i32,
+    /// Event identification.
+    pub event_id: i64,
+    /// Period of the match, e.g. 0 for the match, 1 for the 1st half.
+    pub period_number: i32,
+    /// Type of the bet.
+    pub bet_type: BetType,
+    /// Chosen team, for moneyline, spread and team total bets.
+    pub team: Option<Team>,
+    /// Chosen side, for total and team total bets.
+    pub side: Option<BetSide>,
+}
+
+impl PinnacleApiRequest for GetLine {
+    const PATH: &'static str = "/v2/line";
+    type Response = LineResponse;
+}
+
+/// Whether the stake is the amount to risk or to win
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum WinRiskStake {
+    /// The stake is the amount to win
+    Win,
+    /// The stake is the amount to risk
+    Risk,
+}
+
+/// How to handle the stake exceeding the limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum FillType {
+    /// The bet is rejected if the stake exceeds the limit
+    #[default]
+    Normal,
+    /// The stake is reduced to the limit
+    FillAndKill,
+    /// The stake is ignored and the bet is placed for the maximum allowed amount
+    FillMaxLimit,
+}
+
+/// Type of a bet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BetType {
+    /// Moneyline
+    Moneyline,
+    /// Team total points
+    TeamTotalPoints,
+    /// Spread
+    Spread,
+    /// Total points
+    TotalPoints,
+}
+
+/// Team of a bet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Team {
+    /// Team 1
+    Team1,
+    /// Team 2
+    Team2,
+    /// Draw
+    Draw,
+}
+
+/// Side of a total bet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum BetSide {
+    /// Over
+    Over,
+    /// Under
+    Under,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -164,4 +339,28 @@ mod tests {
             "sportId=0&leagueIds=1%2C2&oddsFormat=Decimal"
         );
     }
+
+    #[test]
+    fn test_place_straight_bet_request() {
+        let bet = PlaceStraightBet {
+            odds_format: OddsFormat::Decimal,
+            unique_request_id: "id".into(),
+            accept_better_line: true,
+            stake: 10.0,
+            win_risk_stake: WinRiskStake::Risk,
+            line_id: 1,
+            alt_line_id: None,
+            fill_type: FillType::FillAndKill,
+            sport_id: 29,
+            event_id: 2,
+            period_number: 0,
+            bet_type: BetType::TotalPoints,
+            team: None,
+            side: Some(BetSide::Over),
+        };
+        assert_eq!(
+            serde_json::to_string(&bet).unwrap(),
+            r#"{"oddsFormat":"DECIMAL","uniqueRequestId":"id","acceptBetterLine":true,"stake":10.0,"winRiskStake":"RISK","lineId":1,"fillType":"FILLANDKILL","sportId":29,"eventId":2,"periodNumber":0,"betType":"TOTAL_POINTS","side":"OVER"}"#
+        );
+    }
 }
@@ -1,7 +1,46 @@
 //! Typed Pinnacle API responses
+//!
+//! Pinnacle adds fields over time. With the `extra-fields` feature every struct captures the
+//! fields it doesn't model into its `extra` map, and the client warns about them once.
+use crate::requests::{BetSide, BetType};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// Fields of a response struct it doesn't model, requires the `extra-fields` feature
+#[cfg(feature = "extra-fields")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtraFields(pub serde_json::Map<String, serde_json::Value>);
+
+#[cfg(feature = "extra-fields")]
+impl std::ops::Deref for ExtraFields {
+    type Target = serde_json::Map<String, serde_json::Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "extra-fields")]
+impl std::ops::DerefMut for ExtraFields {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(feature = "extra-fields")]
+impl<'de> Deserialize<'de> for ExtraFields {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        crate::util::deserialize_extra(deserializer).map(Self)
+    }
+}
+
+#[cfg(feature = "extra-fields")]
+impl Serialize for ExtraFields {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        crate::util::serialize_extra(&self.0, serializer)
+    }
+}
+
 /// Represents the balance details of a client.
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +53,10 @@ pub struct ClientBalanceResponse {
     pub given_credit: f64,
     /// The client's currency code.
     pub currency: String,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a sports response containing a list of sports.
@@ -22,6 +65,10 @@ pub struct ClientBalanceResponse {
 pub struct SportsResponse {
     /// The list of sports.
     pub sports: Vec<Sport>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a sport.
@@ -40,6 +87,10 @@ pub struct Sport {
     pub event_specials_count: i32,
     /// Indicates how many events are in the given sport.
     pub event_count: i32,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a leagues response containing a list of leagues.
@@ -48,6 +99,10 @@ pub struct Sport {
 pub struct Leagues {
     /// The list of leagues.
     pub leagues: Vec<League>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a league.
@@ -72,6 +127,10 @@ pub struct League {
     pub event_specials_count: i32,
     /// Indicates how many events are in the given league.
     pub event_count: i32,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Periods
@@ -79,6 +138,10 @@ pub struct League {
 pub struct SportPeriods {
     /// Periods
     pub periods: Vec<SportPeriod>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a period for a sport.
@@ -111,6 +174,10 @@ pub struct SportPeriod {
     pub team1_total_short_description: String,
     /// Short description for team2 totals.
     pub team2_total_short_description: String,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Response
@@ -124,6 +191,10 @@ pub struct OddsResponse {
     pub last: i64,
     /// Contains a list of Leagues.
     pub leagues: Vec<OddsLeague>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds League
@@ -134,6 +205,10 @@ pub struct OddsLeague {
     pub id: i32,
     /// Contains a list of events.
     pub events: Vec<OddsEvent>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Event
@@ -156,6 +231,10 @@ pub struct OddsEvent {
     pub home_red_cards: Option<i32>,
     /// Contains a list of periods.
     pub periods: Vec<OddsPeriod>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Period
@@ -206,6 +285,10 @@ pub struct OddsPeriod {
     /// Period home team red cards. Only for live soccer events. Supported only for Match
     /// (number=0) and Extra Time (number=3).
     pub home_red_cards: Option<i32>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Spread
@@ -223,6 +306,10 @@ pub struct OddsSpread {
     /// Maximum bet volume. Present only on alternative lines, if set it overrides `maxSpread`
     /// market limit.
     pub max: Option<f64>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Moneyline
@@ -235,6 +322,10 @@ pub struct OddsMoneyline {
     pub away: f64,
     /// Draw price. This is present only for events we offer price for draw.
     pub draw: Option<f64>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Total
@@ -252,6 +343,10 @@ pub struct OddsTotal {
     /// Maximum bet volume. Present only on alternative lines, if set it overrides `maxTotal`
     /// market limit.
     pub max: Option<f64>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Team Totals
@@ -262,6 +357,10 @@ pub struct OddsTeamTotals {
     pub home: Option<OddsTeamTotal>,
     /// Away team total points, over and under prices.
     pub away: Option<OddsTeamTotal>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Odds Team Total
@@ -274,6 +373,10 @@ pub struct OddsTeamTotal {
     pub over: f64,
     /// Under price.
     pub under: f64,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents the response from the /v1/fixtures endpoint.
@@ -287,6 +390,10 @@ pub struct FixturesResponse {
     pub last: i64,
     /// Contains a list of leagues.
     pub league: Vec<FixturesLeague>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Represents a league in the fixtures response.
@@ -301,6 +408,10 @@ pub struct FixturesLeague {
 
     /// Contains a list of events.
     pub events: Vec<Fixture>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
 }
 
 /// Fixture object
@@ -325,6 +436,7 @@ pub struct Fixture {
     /// - 0 = No live betting will be offered on this event.
     /// - 1 = Live betting event.
     /// - 2 = Live betting will be offered on this match, but on a different event.
+    ///
     /// Please note that pre-game and live events are different.
     pub live_status: i32,
     /// Home team pitcher. Only for Baseball.
@@ -340,6 +452,7 @@ pub struct Fixture {
     /// - 0 = Allowed to parlay, without restrictions.
     /// - 1 = Not allowed to parlay this event.
     /// - 2 = Allowed to parlay with the restrictions. You cannot have more than one leg from the same event in the parlay.
+    ///
     /// All events with the same rotation number are treated as same event.
     pub parlay_restriction: i32,
     /// Whether an event is offer with alternative teaser points. Events with alternative teaser points may vary from teaser definition.
@@ -348,4 +461,231 @@ pub struct Fixture {
     pub resulting_unit: Option<String>,
     /// Fixture version, goes up when there is a change in the fixture.
     pub version: i64,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Response of the settled fixtures request
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettledFixturesResponse {
+    /// Same as requested sport ID.
+    pub sport_id: i32,
+    /// Use this value for the subsequent requests for since query parameter to get just the
+    /// changes since previous response.
+    pub last: i64,
+    /// Contains a list of leagues.
+    pub leagues: Vec<SettledLeague>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// A league of the settled fixtures
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettledLeague {
+    /// League ID.
+    pub id: i32,
+    /// Contains a list of events.
+    pub events: Vec<SettledEvent>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// A settled event
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettledEvent {
+    /// Event id.
+    pub id: i64,
+    /// Contains a list of settled periods.
+    pub periods: Vec<SettledPeriod>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// A settled period of an event
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettledPeriod {
+    /// This represents the period of the match.
+    pub number: i32,
+    /// Period settlement status.
+    /// - 1 = Event period is settled.
+    /// - 2 = Event period is re-settled.
+    /// - 3 = Event period is cancelled.
+    /// - 4 = Event period is re-settled as cancelled.
+    /// - 5 = Event is deleted.
+    pub status: i32,
+    /// Unique id of the settlement. In case of a re-settlement, a new settlement id is used.
+    pub settlement_id: i64,
+    /// Date and time in UTC when the period was settled.
+    pub settled_at: DateTime<Utc>,
+    /// Team1 score.
+    pub team1_score: Option<f64>,
+    /// Team2 score.
+    pub team2_score: Option<f64>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Response of the get line request
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineResponse {
+    /// Whether the line exists.
+    pub status: LineStatus,
+    /// Latest price.
+    pub price: Option<f64>,
+    /// Line identification needed to place a bet.
+    pub line_id: Option<i64>,
+    /// This is present only if it's an alternative line.
+    pub alt_line_id: Option<i64>,
+    /// Minimum bettable risk amount.
+    pub min_risk_stake: Option<f64>,
+    /// Maximum bettable risk amount.
+    pub max_risk_stake: Option<f64>,
+    /// Minimum bettable win amount.
+    pub min_win_stake: Option<f64>,
+    /// Maximum bettable win amount.
+    pub max_win_stake: Option<f64>,
+    /// Date time the line is effective as of.
+    pub effective_as_of: Option<DateTime<Utc>>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Status of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LineStatus {
+    /// The line is available
+    Success,
+    /// The line doesn't exist anymore
+    NotExists,
+}
+
+/// Response of the straight bet placement
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceBetResponse {
+    /// Status of the request.
+    pub status: PlaceBetStatus,
+    /// Reason of the failure, if the status is `PROCESSED_WITH_ERROR`, e.g. `LINE_CHANGED`,
+    /// `ABOVE_MAX_BET_AMOUNT`, `INSUFFICIENT_FUNDS`.
+    pub error_code: Option<String>,
+    /// Unique id of the request, same as in the bet.
+    pub unique_request_id: String,
+    /// The placed bet, present unless the status is `PROCESSED_WITH_ERROR`.
+    pub straight_bet: Option<StraightBet>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Status of the bet placement request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlaceBetStatus {
+    /// The bet is accepted
+    Accepted,
+    /// The bet is a live bet waiting for the live delay to pass
+    PendingAcceptance,
+    /// The bet isn't placed, see the error code
+    ProcessedWithError,
+}
+
+/// A straight bet
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StraightBet {
+    /// Bet identification.
+    pub bet_id: i64,
+    /// Unique id of the request the bet was placed with.
+    pub unique_request_id: Option<String>,
+    /// Wager identification, all bets placed on a single line share the same wager number.
+    pub wager_number: i32,
+    /// Date time when the bet was placed.
+    pub placed_at: DateTime<Utc>,
+    /// Status of the bet.
+    pub bet_status: BetStatus,
+    /// Type of the bet.
+    pub bet_type: BetType,
+    /// Win amount.
+    pub win: f64,
+    /// Risk amount.
+    pub risk: f64,
+    /// Win-loss of a settled bet.
+    pub win_loss: Option<f64>,
+    /// Price of the bet.
+    pub price: f64,
+    /// Sport identification.
+    pub sport_id: i32,
+    /// League identification.
+    pub league_id: Option<i32>,
+    /// Event identification.
+    pub event_id: i64,
+    /// Handicap of the bet, for spread and total bets.
+    pub handicap: Option<f64>,
+    /// Name of the chosen team.
+    pub team_name: Option<String>,
+    /// Chosen side, for total and team total bets.
+    pub side: Option<BetSide>,
+    /// Period of the match.
+    pub period_number: i32,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Response of the get bets request
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BetsResponse {
+    /// Straight bets.
+    #[serde(default)]
+    pub straight_bets: Vec<StraightBet>,
+    /// Fields the struct doesn't model.
+    #[cfg(feature = "extra-fields")]
+    #[serde(flatten)]
+    pub extra: ExtraFields,
+}
+
+/// Status of a bet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BetStatus {
+    /// The bet is accepted
+    Accepted,
+    /// The bet is cancelled
+    Cancelled,
+    /// The bet is lost
+    Lose,
+    /// The live bet is waiting for the live delay to pass
+    PendingAcceptance,
+    /// The bet is refunded
+    Refunded,
+    /// The live bet isn't accepted after the live delay
+    NotAccepted,
+    /// The bet is won
+    Won,
+    /// The bet is rejected
+    Rejected,
+    /// A status not known to this version of the crate
+    #[serde(other)]
+    Unknown,
 }
@@ -0,0 +1,491 @@
+//! Detects drift between the API responses and the response structs.
+//!
+//! Pinnacle Open API definition doesn't differentiate non optional response fields, and new
+//! fields and enum variants appear over time. [`check_response`] reports every field of a
+//! response that fails to decode, not only the first one, and every field the structs don't
+//! model. [`SchemaChecker`] runs the wrapped requests across all the sports, against the live
+//! API or recorded cassettes. Unsuccessful responses, e.g. due to wrong credentials, are
+//! reported as [`IssueKind::Status`] as they leave the structs unchecked:
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleReplayClient::replay("tests/cassettes");
+//! let issues = SchemaChecker::new(&client)
+//!     .with_progress(|i, n, sport| eprintln!("{i} of {n}: {}", sport.name))
+//!     .check_all()
+//!     .await?;
+//! for summary in summarize(&issues) {
+//!     println!("{summary}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+use crate::client::RawResponse;
+use crate::requests::{
+    GetClientBalance, GetFixtures, GetLeagues, GetPeriods, GetSettledFixtures, GetSports,
+    GetStraightOdds,
+};
+use crate::responses::{Sport, SportsResponse};
+use crate::traits::{request_url, PinnacleApiClient, PinnacleApiRequest};
+use crate::util::parse_json;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use serde_path_to_error::Segment;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+/// Decoding errors reported per response at most, the response is malformed beyond that
+const MAX_ERRORS: usize = 1000;
+/// Characters of the body of an unsuccessful response kept in its issue
+const MAX_BODY_CHARS: usize = 200;
+
+/// A field of a response not matching its struct
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaIssue {
+    /// Path of the endpoint
+    pub endpoint: &'static str,
+    /// Requested URL
+    pub url: String,
+    /// Path of the field, e.g. `leagues[0].events[2].periods`
+    pub path: String,
+    /// What's wrong with the field
+    pub kind: IssueKind,
+    /// Decoding error or the value of the field
+    pub message: String,
+}
+
+/// What's wrong with a field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IssueKind {
+    /// Null in a non optional field
+    Null,
+    /// A non optional field is missing
+    Missing,
+    /// An enum variant the crate doesn't know
+    UnknownVariant,
+    /// Any other decoding error
+    Invalid,
+    /// A field the response struct doesn't model
+    Unmodeled,
+    /// The response status isn't successful, so the response couldn't be checked
+    Status,
+}
+
+/// Issues of the same field of an endpoint, with array indexes dropped from the path
+#[derive(Debug, Clone)]
+pub struct IssueSummary {
+    /// Path of the endpoint
+    pub endpoint: &'static str,
+    /// Path of the field, e.g. `leagues[].events[].periods`
+    pub path: String,
+    /// What's wrong with the field
+    pub kind: IssueKind,
+    /// How many times the issue occurred
+    pub count: usize,
+    /// The first occurrence
+    pub example: SchemaIssue,
+}
+
+/// Called by [`SchemaChecker::check_all`] before checking a sport with its 1-based number and
+/// the number of the sports
+type Progress<'a> = Box<dyn Fn(usize, usize, &Sport) + Send + Sync + 'a>;
+
+/// Runs the wrapped requests and checks their responses
+pub struct SchemaChecker<'a, C> {
+    client: &'a C,
+    progress: Option<Progress<'a>>,
+}
+
+impl<C> fmt::Debug for SchemaChecker<'_, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SchemaChecker").finish_non_exhaustive()
+    }
+}
+
+impl<'a, C> SchemaChecker<'a, C>
+where
+    C: PinnacleApiClient + Sync,
+{
+    /// Creates a checker sending the requests with the client
+    pub fn new(client: &'a C) -> Self {
+        Self {
+            client,
+            progress: None,
+        }
+    }
+
+    /// Reports the progress of [`check_all`](Self::check_all), calling `progress` with the
+    /// 1-based number of the sport, the number of the sports and the sport before checking it
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(usize, usize, &Sport) + Send + Sync + 'a,
+    ) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Checks the response of a request
+    pub async fn check<Q>(&self, query: &Q) -> Result<Vec<SchemaIssue>, C::Error>
+    where
+        Q: PinnacleApiRequest + Serialize + Sync,
+    {
+        let url = request_url(query);
+        let response = self.client.get_raw(url.as_str()).await?;
+        Ok(check_raw_response::<Q::Response>(Q::PATH, &url, &response))
+    }
+
+    /// Checks the balance and sports, then leagues, periods, fixtures, settled fixtures and
+    /// odds of every sport with offerings
+    pub async fn check_all(&self) -> Result<Vec<SchemaIssue>, C::Error> {
+        let mut issues = self.check(&GetClientBalance).await?;
+        let url = request_url(&GetSports);
+        let response = self.client.get_raw(url.as_str()).await?;
+        issues.extend(check_raw_response::<SportsResponse>(
+            GetSports::PATH,
+            &url,
+            &response,
+        ));
+        // Sports are checked above, so a response failing to decode just has nothing to loop
+        let sports: Vec<_> = parse_json::<SportsResponse>(&response.body)
+            .map(|resp| resp.sports)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|sport| sport.has_offerings)
+            .collect();
+        for (i, sport) in sports.iter().enumerate() {
+            if let Some(progress) = &self.progress {
+                progress(i + 1, sports.len(), sport);
+            }
+            let sport_id = sport.id;
+            issues.extend(self.check(&GetLeagues { sport_id }).await?);
+            issues.extend(self.check(&GetPeriods { sport_id }).await?);
+            let fixtures = GetFixtures {
+                sport_id,
+                ..Default::default()
+            };
+            issues.extend(self.check(&fixtures).await?);
+            let settled = GetSettledFixtures {
+                sport_id,
+                ..Default::default()
+            };
+            issues.extend(self.check(&settled).await?);
+            let odds = GetStraightOdds {
+                sport_id,
+                ..Default::default()
+            };
+            issues.extend(self.check(&odds).await?);
+        }
+        Ok(issues)
+    }
+}
+
+/// Checks a raw response, an unsuccessful one is reported as a [`IssueKind::Status`] issue,
+/// an empty one has nothing to check
+pub fn check_raw_response<T>(
+    endpoint: &'static str,
+    url: &str,
+    response: &RawResponse,
+) -> Vec<SchemaIssue>
+where
+    T: DeserializeOwned + Serialize,
+{
+    if !(200..300).contains(&response.status) {
+        let body: String = response.body.chars().take(MAX_BODY_CHARS).collect();
+        return vec![SchemaIssue {
+            endpoint,
+            url: url.to_string(),
+            path: ".".into(),
+            kind: IssueKind::Status,
+            message: format!("HTTP {}: {body}", response.status),
+        }];
+    }
+    if response.body.is_empty() {
+        return Vec::new();
+    }
+    check_response::<T>(endpoint, url, &response.body)
+}
+
+/// Checks a response body against its struct.
+///
+/// After a decoding error the array element containing the field is dropped and decoding
+/// continues, so every broken field is reported. Fields of the decoded elements missing from
+/// the struct re-encoded back to json are reported as unmodeled.
+pub fn check_response<T>(endpoint: &'static str, url: &str, body: &str) -> Vec<SchemaIssue>
+where
+    T: DeserializeOwned + Serialize,
+{
+    let issue = |path: String, kind, message: String| SchemaIssue {
+        endpoint,
+        url: url.to_string(),
+        path,
+        kind,
+        message,
+    };
+    let original: Value = match serde_json::from_str(body) {
+        Ok(json) => json,
+        Err(e) => return vec![issue(".".into(), IssueKind::Invalid, e.to_string())],
+    };
+
+    let mut json = original.clone();
+    // Paths of the dropped elements, with their indexes in the original json
+    let mut removed = HashSet::new();
+    let mut issues = Vec::new();
+    let decoded = loop {
+        let error = match serde_path_to_error::deserialize::<_, T>(&json) {
+            Ok(decoded) => break decoded,
+            Err(e) => e,
+        };
+        let segments: Vec<_> = error.path().iter().cloned().collect();
+        let mut original_segments = original_segments(&original, &segments, &removed);
+        let message = error.inner().to_string();
+        let (kind, field) = classify(&message);
+        if let Some(key) = field {
+            original_segments.push(Segment::Map { key });
+        }
+        issues.push(issue(path_string(&original_segments), kind, message));
+
+        if issues.len() >= MAX_ERRORS {
+            return issues;
+        }
+        let Some(element) = remove_element(&mut json, &segments) else {
+            return issues;
+        };
+        removed.insert(path_string(&original_segments[..=element]));
+    };
+
+    // Captured unmodeled fields would be encoded back and look modeled
+    #[cfg(feature = "extra-fields")]
+    let model = crate::util::without_extra_fields(|| serde_json::to_value(&decoded));
+    #[cfg(not(feature = "extra-fields"))]
+    let model = serde_json::to_value(&decoded);
+    if let Ok(model) = model {
+        let mut unmodeled = Vec::new();
+        compare(&original, &model, String::new(), &removed, &mut unmodeled);
+        issues.extend(
+            unmodeled
+                .into_iter()
+                .map(|(path, kind, value)| issue(path, kind, value)),
+        );
+    }
+    issues
+}
+
+/// Groups the issues by endpoint, field and kind
+pub fn summarize(issues: &[SchemaIssue]) -> Vec<IssueSummary> {
+    let mut groups: BTreeMap<_, IssueSummary> = BTreeMap::new();
+    for issue in issues {
+        let path = generic_path(&issue.path);
+        groups
+            .entry((issue.endpoint, path.clone(), issue.kind))
+            .and_modify(|summary| summary.count += 1)
+            .or_insert_with(|| IssueSummary {
+                endpoint: issue.endpoint,
+                path,
+                kind: issue.kind,
+                count: 1,
+                example: issue.clone(),
+            });
+    }
+    groups.into_values().collect()
+}
+
+impl fmt::Display for IssueSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {:?} x{}: {}\n\t{}",
+            self.endpoint, self.path, self.kind, self.count, self.example.message, self.example.url
+        )
+    }
+}
+
+/// Kind of a decoding error by its message, with the field name of a missing field
+fn classify(message: &str) -> (IssueKind, Option<String>) {
+    if message.starts_with("invalid type: null") {
+        (IssueKind::Null, None)
+    } else if let Some(rest) = message.strip_prefix("missing field `") {
+        (
+            IssueKind::Missing,
+            rest.split('`').next().map(str::to_string),
+        )
+    } else if message.starts_with("unknown variant") {
+        (IssueKind::UnknownVariant, None)
+    } else {
+        (IssueKind::Invalid, None)
+    }
+}
+
+/// Removes the innermost array element containing the path, returns the position of its
+/// segment
+fn remove_element(json: &mut Value, segments: &[Segment]) -> Option<usize> {
+    let last_seq = segments
+        .iter()
+        .rposition(|s| matches!(s, Segment::Seq { .. }))?;
+    let mut current = json;
+    for segment in &segments[..last_seq] {
+        current = match segment {
+            Segment::Seq { index } => current.get_mut(*index)?,
+            Segment::Map { key } => current.get_mut(key.as_str())?,
+            Segment::Enum { variant } if current.is_object() => {
+                current.get_mut(variant.as_str())?
+            }
+            Segment::Enum { .. } => current,
+            Segment::Unknown => return None,
+        };
+    }
+    match (current, &segments[last_seq]) {
+        (Value::Array(items), Segment::Seq { index }) if *index < items.len() => {
+            items.remove(*index);
+            Some(last_seq)
+        }
+        _ => None,
+    }
+}
+
+/// Maps array indexes of a path in the json with removed elements to the original json
+fn original_segments(
+    original: &Value,
+    segments: &[Segment],
+    removed: &HashSet<String>,
+) -> Vec<Segment> {
+    let mut current = Some(original);
+    let mut mapped = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let segment = match (segment, current) {
+            (Segment::Seq { index }, Some(Value::Array(items))) => {
+                let prefix = path_string(&mapped);
+                (0..items.len())
+                    .filter(|i| !removed.contains(&format!("{prefix}[{i}]")))
+                    .nth(*index)
+                    .map_or(segment.clone(), |index| Segment::Seq { index })
+            }
+            _ => segment.clone(),
+        };
+        current = current.and_then(|value| match &segment {
+            Segment::Seq { index } => value.get(*index),
+            Segment::Map { key } => value.get(key.as_str()),
+            Segment::Enum { variant } => value.get(variant.as_str()).or(Some(value)),
+            Segment::Unknown => None,
+        });
+        mapped.push(segment);
+    }
+    mapped
+}
+
+/// Collects non null fields of the json missing in the re-encoded model, and values of enums
+/// decoded into an unknown variant
+fn compare(
+    json: &Value,
+    model: &Value,
+    path: String,
+    removed: &HashSet<String>,
+    out: &mut Vec<(String, IssueKind, String)>,
+) {
+    match (json, model) {
+        (Value::Object(fields), Value::Object(modeled)) => {
+            for (key, value) in fields {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                match modeled.get(key) {
+                    Some(model) => compare(value, model, field_path, removed, out),
+                    None if value.is_null() => (),
+                    None => out.push((field_path, IssueKind::Unmodeled, value.to_string())),
+                }
+            }
+        }
+        (Value::Array(items), Value::Array(modeled)) => {
+            let items = items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| (format!("{path}[{i}]"), item))
+                .filter(|(item_path, _)| !removed.contains(item_path));
+            for ((item_path, item), model) in items.zip(modeled) {
+                compare(item, model, item_path, removed, out);
+            }
+        }
+        (Value::String(value), Value::String(modeled))
+            if value != modeled && modeled == "UNKNOWN" =>
+        {
+            out.push((path, IssueKind::UnknownVariant, value.clone()));
+        }
+        _ => (),
+    }
+}
+
+/// Formats the path the way [`serde_path_to_error::Path`] does
+fn path_string(segments: &[Segment]) -> String {
+    let mut path = String::new();
+    for segment in segments {
+        if !path.is_empty() && !matches!(segment, Segment::Seq { .. }) {
+            path.push('.');
+        }
+        path.push_str(&segment.to_string());
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+    path
+}
+
+/// Drops array indexes from the path, e.g. `leagues[0].id` becomes `leagues[].id`
+fn generic_path(path: &str) -> String {
+    let mut generic = String::with_capacity(path.len());
+    let mut in_index = false;
+    for c in path.chars() {
+        match c {
+            '[' => in_index = true,
+            ']' => in_index = false,
+            _ if in_index => continue,
+            _ => (),
+        }
+        generic.push(c);
+    }
+    generic
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_response() {
+        let body = r#"{"sports": [
+            {"id": 1, "name": "A", "hasOfferings": true, "leagueSpecialsCount": 0,
+             "eventSpecialsCount": 0, "eventCount": null},
+            {"id": 2, "name": "B", "hasOfferings": true, "leagueSpecialsCount": 0,
+             "eventSpecialsCount": 0},
+            {"id": 3, "name": "C", "hasOfferings": true, "leagueSpecialsCount": 0,
+             "eventSpecialsCount": 0, "eventCount": 1, "isNew": true}
+        ]}"#;
+        let issues = check_response::<SportsResponse>("/v2/sports", "url", body);
+        let found: Vec<_> = issues.iter().map(|i| (i.path.as_str(), i.kind)).collect();
+        assert_eq!(
+            found,
+            [
+                ("sports[0].eventCount", IssueKind::Null),
+                ("sports[1].eventCount", IssueKind::Missing),
+                ("sports[2].isNew", IssueKind::Unmodeled),
+            ]
+        );
+
+        let summary = summarize(&issues);
+        assert_eq!(summary.len(), 3);
+        assert!(summary.iter().any(|s| s.path == "sports[].eventCount"));
+
+        let response = RawResponse {
+            status: 401,
+            headers: Default::default(),
+            body: r#"{"code":"INVALID_CREDENTIALS"}"#.into(),
+        };
+        let issues = check_raw_response::<SportsResponse>("/v2/sports", "url", &response);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].kind, IssueKind::Status);
+        assert!(issues[0].message.starts_with("HTTP 401"));
+    }
+}
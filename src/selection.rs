@@ -0,0 +1,336 @@
+//! From a price in the odds to a placed bet.
+//!
+//! A [`Selection`] is a single price of an [`OddsPeriod`] mapped onto the bet terms: bet type,
+//! `TEAM1`/`TEAM2` instead of home/away, side, handicap and alternative line. It fetches the
+//! live line with [`GetLine`] and places the bet only if the price hasn't dropped beyond a
+//! tolerance. Prices are expected to be in the decimal format.
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = PinnacleClient::new("pinnacle_user", "pinnacle_password");
+//! # let league: League = serde_json::from_str(
+//! #     r#"{"id": 1980, "name": "England - Premier League", "homeTeamType": "Team1",
+//! #     "hasOfferings": true, "container": "England", "allowRoundRobins": false,
+//! #     "leagueSpecialsCount": 0, "eventSpecialsCount": 0, "eventCount": 1}"#,
+//! # )?;
+//! # let odds_event: OddsEvent = serde_json::from_str(r#"{"id": 10, "periods": []}"#)?;
+//! # let period: OddsPeriod = serde_json::from_str(
+//! #     r#"{"lineId": 100, "number": 0, "cutoff": "2023-04-16T18:00:00Z", "status": 1,
+//! #     "moneyline": {"home": 2.1, "away": 3.5, "draw": 3.2}}"#,
+//! # )?;
+//! for price in period.prices() {
+//!     let selection = Selection::new(29, &league, odds_event.id, &period, &price);
+//!     // Accept the price dropping by 0.05 at most
+//!     let resp = selection.place(&client, 10.0, 0.05).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`OddsPeriod`]: crate::responses::OddsPeriod
+use crate::client::PinnacleClientError;
+use crate::market_book::{MarketType, Price, PricedSelection, Side};
+use crate::requests::{
+    BetSide, BetType, FillType, GetLine, OddsFormat, PlaceStraightBet, Team, WinRiskStake,
+};
+use crate::responses::{League, LineResponse, LineStatus, OddsPeriod, PlaceBetResponse};
+use crate::traits::PinnacleApiClient;
+use displaydoc::Display;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+/// A single price of a period in the terms of a bet
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Selection {
+    /// Sport identification.
+    pub sport_id: i32,
+    /// League identification.
+    pub league_id: i32,
+    /// Event identification.
+    pub event_id: i64,
+    /// Period of the match.
+    pub period_number: i32,
+    /// Line identification as seen in the odds.
+    pub line_id: i64,
+    /// This is present only if it's an alternative line.
+    pub alt_line_id: Option<i64>,
+    /// Type of the bet.
+    pub bet_type: BetType,
+    /// Chosen team, for moneyline, spread and team total bets.
+    pub team: Option<Team>,
+    /// Chosen side, for total and team total bets.
+    pub side: Option<BetSide>,
+    /// Handicap of the chosen team for spreads, points for totals.
+    pub handicap: Option<f64>,
+    /// Price as seen in the odds.
+    pub price: f64,
+}
+
+/// Errors
+#[derive(Debug, Display, Error)]
+pub enum SelectionError<E> {
+    /// client
+    Client(#[source] E),
+    /// line of event {0} isn't available
+    LineNotAvailable(i64),
+    /// live line of event {0} has no line id
+    MissingLineId(i64),
+    /// price dropped from {expected} to {actual}
+    PriceMoved {
+        /// Price as seen in the odds
+        expected: f64,
+        /// Price of the live line
+        actual: f64,
+    },
+}
+
+impl Selection {
+    /// Creates a selection from a price of the period, e.g. one of [`OddsPeriod::prices`]. The
+    /// league is needed to tell whether the home team is `TEAM1` or `TEAM2`.
+    pub fn new(
+        sport_id: i32,
+        league: &League,
+        event_id: i64,
+        period: &OddsPeriod,
+        price: &Price,
+    ) -> Self {
+        let home = home_team(&league.home_team_type);
+        Self::with_home_team(
+            sport_id,
+            league.id,
+            event_id,
+            period.number,
+            period.line_id,
+            price,
+            home,
+        )
+    }
+
+    /// Creates a selection from a price of a [`MarketBook`](crate::market_book::MarketBook),
+    /// `home_team_type` is the one of the [`League`]
+    pub fn from_priced(sport_id: i32, selection: &PricedSelection, home_team_type: &str) -> Self {
+        Self::with_home_team(
+            sport_id,
+            selection.event.league_id,
+            selection.event.id,
+            selection.market.number,
+            selection.market.line_id,
+            selection.price,
+            home_team(home_team_type),
+        )
+    }
+
+    fn with_home_team(
+        sport_id: i32,
+        league_id: i32,
+        event_id: i64,
+        period_number: i32,
+        line_id: i64,
+        price: &Price,
+        home: Team,
+    ) -> Self {
+        let away = match home {
+            Team::Team1 => Team::Team2,
+            _ => Team::Team1,
+        };
+        let team = match price.side {
+            Side::Home => Some(home),
+            Side::Away => Some(away),
+            Side::Draw => Some(Team::Draw),
+            Side::Over | Side::Under => None,
+        };
+        let side = match price.side {
+            Side::Over => Some(BetSide::Over),
+            Side::Under => Some(BetSide::Under),
+            _ => None,
+        };
+        let (bet_type, team) = match price.market_type {
+            MarketType::Moneyline => (BetType::Moneyline, team),
+            MarketType::Spread => (BetType::Spread, team),
+            MarketType::Total => (BetType::TotalPoints, None),
+            MarketType::HomeTeamTotal => (BetType::TeamTotalPoints, Some(home)),
+            MarketType::AwayTeamTotal => (BetType::TeamTotalPoints, Some(away)),
+        };
+        Self {
+            sport_id,
+            league_id,
+            event_id,
+            period_number,
+            line_id,
+            alt_line_id: price.alt_line_id,
+            bet_type,
+            team,
+            side,
+            handicap: price.line,
+            price: price.price,
+        }
+    }
+
+    /// Request of the live line of the selection
+    pub fn line_request(&self) -> GetLine {
+        GetLine {
+            league_id: self.league_id,
+            handicap: self.handicap,
+            odds_format: OddsFormat::Decimal,
+            sport_id: self.sport_id,
+            event_id: self.event_id,
+            period_number: self.period_number,
+            bet_type: self.bet_type,
+            team: self.team,
+            side: self.side,
+        }
+    }
+
+    /// Bet risking the `stake` at the live line, with a newly generated unique request id.
+    /// The line ids are taken from the live line only, `None` if it has no line id.
+    pub fn bet(&self, line: &LineResponse, stake: f64) -> Option<PlaceStraightBet> {
+        Some(PlaceStraightBet {
+            odds_format: OddsFormat::Decimal,
+            unique_request_id: Uuid::new_v4().to_string(),
+            accept_better_line: true,
+            stake,
+            win_risk_stake: WinRiskStake::Risk,
+            line_id: line.line_id?,
+            alt_line_id: line.alt_line_id,
+            fill_type: FillType::Normal,
+            sport_id: self.sport_id,
+            event_id: self.event_id,
+            period_number: self.period_number,
+            bet_type: self.bet_type,
+            team: self.team,
+            side: self.side,
+        })
+    }
+
+    /// Fetches the live line, returns it if its price is at most `max_price_drop` below the
+    /// price of the selection
+    pub async fn check_line<C>(
+        &self,
+        client: &C,
+        max_price_drop: f64,
+    ) -> Result<LineResponse, SelectionError<C::Error>>
+    where
+        C: PinnacleApiClient + Sync,
+        C::Error: Send,
+    {
+        let line = client
+            .get(&self.line_request())
+            .await
+            .map_err(SelectionError::Client)?;
+        let price = match (line.status, line.price) {
+            (LineStatus::Success, Some(price)) => price,
+            _ => return Err(SelectionError::LineNotAvailable(self.event_id)),
+        };
+        if price < self.price - max_price_drop {
+            return Err(SelectionError::PriceMoved {
+                expected: self.price,
+                actual: price,
+            });
+        }
+        Ok(line)
+    }
+
+    /// Places a bet risking the `stake` if the price hasn't dropped more than `max_price_drop`
+    pub async fn place<C>(
+        &self,
+        client: &C,
+        stake: f64,
+        max_price_drop: f64,
+    ) -> Result<PlaceBetResponse, SelectionError<C::Error>>
+    where
+        C: PinnacleApiClient + Sync,
+        C::Error: From<PinnacleClientError> + Send,
+    {
+        let line = self.check_line(client, max_price_drop).await?;
+        let bet = self
+            .bet(&line, stake)
+            .ok_or(SelectionError::MissingLineId(self.event_id))?;
+        client.post(&bet).await.map_err(SelectionError::Client)
+    }
+}
+
+fn home_team(home_team_type: &str) -> Team {
+    if home_team_type.eq_ignore_ascii_case("team2") {
+        Team::Team2
+    } else {
+        Team::Team1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::parse_json;
+
+    #[test]
+    fn test_selection() {
+        let period: OddsPeriod = parse_json(
+            r#"{"lineId": 100, "number": 1, "cutoff": "2023-04-16T18:00:00Z", "status": 1,
+                "moneyline": {"home": 2.1, "away": 3.5, "draw": 3.2},
+                "spreads": [{"altLineId": 7, "hdp": -1.0, "home": 2.5, "away": 1.5}],
+                "totals": [{"points": 2.5, "over": 1.9, "under": 1.95}],
+                "teamTotal": {"away": {"points": 1.5, "over": 2.2, "under": 1.7}}}"#,
+        )
+        .unwrap();
+        let league: League = parse_json(
+            r#"{"id": 1, "name": "League", "homeTeamType": "Team2", "hasOfferings": true,
+                "container": "", "allowRoundRobins": false, "leagueSpecialsCount": 0,
+                "eventSpecialsCount": 0, "eventCount": 1}"#,
+        )
+        .unwrap();
+        let selections: Vec<_> = period
+            .prices()
+            .iter()
+            .map(|p| Selection::new(29, &league, 10, &period, p))
+            .map(|s| (s.bet_type, s.team, s.side, s.handicap, s.alt_line_id))
+            .collect();
+        use BetType::*;
+        assert_eq!(
+            selections,
+            vec![
+                (Moneyline, Some(Team::Team2), None, None, None),
+                (Moneyline, Some(Team::Team1), None, None, None),
+                (Moneyline, Some(Team::Draw), None, None, None),
+                (Spread, Some(Team::Team2), None, Some(-1.0), Some(7)),
+                (Spread, Some(Team::Team1), None, Some(1.0), Some(7)),
+                (TotalPoints, None, Some(BetSide::Over), Some(2.5), None),
+                (TotalPoints, None, Some(BetSide::Under), Some(2.5), None),
+                (
+                    TeamTotalPoints,
+                    Some(Team::Team1),
+                    Some(BetSide::Over),
+                    Some(1.5),
+                    None
+                ),
+                (
+                    TeamTotalPoints,
+                    Some(Team::Team1),
+                    Some(BetSide::Under),
+                    Some(1.5),
+                    None
+                ),
+            ]
+        );
+
+        let selection = Selection::new(29, &league, 10, &period, &period.prices()[3]);
+        let line: LineResponse =
+            parse_json(r#"{"status": "SUCCESS", "price": 2.45, "lineId": 101, "altLineId": 8}"#)
+                .unwrap();
+        let bet = selection.bet(&line, 10.0).unwrap();
+        assert_eq!((bet.line_id, bet.alt_line_id), (101, Some(8)));
+        assert_eq!(bet.period_number, 1);
+        assert_eq!(bet.team, Some(Team::Team2));
+
+        // The ids of the odds aren't mixed with the ones of the live line
+        let line: LineResponse =
+            parse_json(r#"{"status": "SUCCESS", "price": 2.45, "lineId": 102}"#).unwrap();
+        let bet = selection.bet(&line, 10.0).unwrap();
+        assert_eq!((bet.line_id, bet.alt_line_id), (102, None));
+        let line: LineResponse = parse_json(r#"{"status": "SUCCESS", "price": 2.45}"#).unwrap();
+        assert!(selection.bet(&line, 10.0).is_none());
+    }
+}
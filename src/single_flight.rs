@@ -0,0 +1,255 @@
+//! Pinnacle API client coalescing concurrent identical requests into one, so they don't waste
+//! the rate limit. It wraps any other client, e.g. [`PinnacleClient`](crate::client::PinnacleClient)
+//! or [`PinnacleCachingClient`](crate::caching_client::PinnacleCachingClient).
+//!
+//! ```rust,no_run
+//! use pinnacle::prelude::*;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), PinnacleClientError> {
+//! let client = PinnacleSingleFlightClient::new(PinnacleClient::new("user", "password"));
+//! // Only one request is sent
+//! let (a, b) = tokio::join!(client.get(&GetSports), client.get(&GetSports));
+//! # Ok(())
+//! # }
+//! ```
+use crate::cache_store::canonical_url;
+use crate::client::{PinnacleClientError, RawResponse};
+use crate::traits::PinnacleApiClient;
+use crate::util::error_chain;
+use async_trait::async_trait;
+use reqwest::{IntoUrl, Url};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::Send;
+use std::sync::{Mutex, MutexGuard};
+use tokio::sync::watch;
+
+type SharedResult = Option<Result<RawResponse, String>>;
+type InFlight = HashMap<String, watch::Receiver<SharedResult>>;
+
+/// Pinnacle API client
+#[derive(Debug)]
+pub struct PinnacleSingleFlightClient<C> {
+    client: C,
+    in_flight: InFlightRequests,
+}
+
+/// Requests in flight, shared by the concurrent identical requests
+#[derive(Debug, Default)]
+pub(crate) struct InFlightRequests(Mutex<InFlight>);
+
+impl<C> PinnacleSingleFlightClient<C>
+where
+    C: PinnacleApiClient + Send + Sync,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    /// Wraps the client
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            in_flight: Default::default(),
+        }
+    }
+
+    /// The wrapped client
+    pub fn inner(&self) -> &C {
+        &self.client
+    }
+
+    async fn get_raw_coalesced(&self, url: Url) -> Result<RawResponse, C::Error> {
+        self.in_flight
+            .coalesce(url.clone(), || self.client.get_raw(url))
+            .await
+    }
+}
+
+impl InFlightRequests {
+    /// Fetches the url, unless the same url is already being fetched, in which case its result
+    /// is shared
+    pub(crate) async fn coalesce<F, Fut, E>(&self, url: Url, fetch: F) -> Result<RawResponse, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<RawResponse, E>>,
+        E: std::error::Error + From<PinnacleClientError> + 'static,
+    {
+        let key = canonical_url(&url);
+        let mut fetch = Some(fetch);
+        loop {
+            let role = {
+                let mut in_flight = self.lock();
+                match in_flight.get(&key) {
+                    Some(rx) => Role::Follower(rx.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        in_flight.insert(key.clone(), rx);
+                        Role::Leader(tx)
+                    }
+                }
+            };
+            let mut rx = match role {
+                Role::Leader(tx) => {
+                    // Only a leader fetches and it returns right away, so the fetch is there
+                    let fetch = fetch.take().expect("a single leader");
+                    return self.lead(&key, fetch(), tx).await;
+                }
+                Role::Follower(rx) => rx,
+            };
+            // An error means the leader was cancelled, so try to become the leader
+            let Ok(shared) = rx.wait_for(Option::is_some).await else {
+                continue;
+            };
+            if let Some(result) = shared.clone() {
+                return result.map_err(|e| PinnacleClientError::Coalesced(e, url).into());
+            }
+        }
+    }
+
+    /// Makes the request and shares its result with the followers
+    async fn lead<E: std::error::Error + 'static>(
+        &self,
+        key: &str,
+        fetch: impl Future<Output = Result<RawResponse, E>>,
+        tx: watch::Sender<SharedResult>,
+    ) -> Result<RawResponse, E> {
+        let _guard = InFlightGuard {
+            in_flight: &self.0,
+            key,
+        };
+        let result = fetch.await;
+        let shared = match &result {
+            Ok(raw) => Ok(raw.clone()),
+            Err(e) => Err(error_chain(e)),
+        };
+        tx.send(Some(shared)).ok();
+        result
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InFlight> {
+        lock(&self.0)
+    }
+}
+
+fn lock(in_flight: &Mutex<InFlight>) -> MutexGuard<'_, InFlight> {
+    in_flight.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+enum Role {
+    Leader(watch::Sender<SharedResult>),
+    Follower(watch::Receiver<SharedResult>),
+}
+
+/// Removes the request from the in-flight ones even if the leader is cancelled
+struct InFlightGuard<'a> {
+    in_flight: &'a Mutex<InFlight>,
+    key: &'a str,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        lock(self.in_flight).remove(self.key);
+    }
+}
+
+#[async_trait]
+impl<C> PinnacleApiClient for PinnacleSingleFlightClient<C>
+where
+    C: PinnacleApiClient + Send + Sync,
+    C::Error: From<PinnacleClientError> + Send + 'static,
+{
+    type Error = C::Error;
+
+    async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        let url = url.into_url().map_err(PinnacleClientError::from)?;
+        let raw = self.get_raw_coalesced(url.clone()).await?;
+        Ok(raw.parse(&url)?)
+    }
+
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        let url = url.into_url().map_err(PinnacleClientError::from)?;
+        self.get_raw_coalesced(url).await
+    }
+
+    /// POST requests aren't coalesced
+    async fn post_by_url<U, B, T>(&self, url: U, body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+    {
+        self.client.post_by_url(url, body).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::GetSports;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    #[derive(Default)]
+    struct SlowClient {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl PinnacleApiClient for SlowClient {
+        type Error = PinnacleClientError;
+
+        async fn get_by_url<U, T>(&self, url: U) -> Result<T, Self::Error>
+        where
+            U: IntoUrl + Send,
+            T: DeserializeOwned + Serialize + Send,
+        {
+            let url = url.into_url()?;
+            self.get_raw(url.clone()).await?.parse(&url)
+        }
+
+        async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+        where
+            U: IntoUrl + Send,
+        {
+            let url = url.into_url()?;
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            if url.query().unwrap_or_default().contains("fail") {
+                return Err(PinnacleClientError::EmptyJson(url));
+            }
+            Ok(RawResponse {
+                status: 200,
+                headers: Default::default(),
+                body: r#"{"sports": []}"#.into(),
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_flight() {
+        let client = PinnacleSingleFlightClient::new(SlowClient::default());
+        let (a, b, c) = tokio::join!(
+            client.get(&GetSports),
+            client.get(&GetSports),
+            client.get(&GetSports)
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 1);
+
+        client.get(&GetSports).await.unwrap();
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 2);
+
+        let url = "https://api.pinnacle.com/v2/sports?fail";
+        let (a, b) = tokio::join!(client.get_raw(url), client.get_raw(url));
+        assert!(matches!(a, Err(PinnacleClientError::EmptyJson(_))));
+        assert!(matches!(b, Err(PinnacleClientError::Coalesced(_, _))));
+        assert_eq!(client.inner().calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,163 @@
+//! A [`CacheStore`] keeping all the responses in a single SQLite file, which is easy to move
+//! around and share, unlike thousands of small files of [`FsCacheStore`](crate::cache_store::FsCacheStore).
+//!
+//! Requires the `sqlite` feature.
+use crate::cache_store::{canonical_url, CacheEntry, CacheStore};
+use crate::util::error_chain;
+use reqwest::Url;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS responses (
+    url TEXT PRIMARY KEY,
+    endpoint TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    fetched_at INTEGER NOT NULL,
+    body TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS responses_endpoint ON responses (endpoint);
+CREATE INDEX IF NOT EXISTS responses_fetched_at ON responses (fetched_at);
+";
+
+/// Caches responses in an SQLite database
+#[derive(Debug)]
+pub struct SqliteCacheStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteCacheStore {
+    /// Opens the database file, creating it if necessary
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Creates a store in a temporary in-memory database
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> rusqlite::Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Deletes responses fetched more than `ttl` ago, returns the number of deleted responses
+    pub fn purge(&self, ttl: Duration) -> rusqlite::Result<usize> {
+        let threshold = SystemTime::now()
+            .checked_sub(ttl)
+            .map(to_millis)
+            .unwrap_or_default();
+        self.lock()
+            .execute("DELETE FROM responses WHERE fetched_at < ?1", [threshold])
+    }
+
+    /// Rebuilds the database file to reclaim the space freed by [`Self::purge`]
+    pub fn vacuum(&self) -> rusqlite::Result<()> {
+        self.lock().execute_batch("VACUUM")
+    }
+
+    /// Number of cached responses
+    pub fn count(&self) -> rusqlite::Result<usize> {
+        self.lock()
+            .query_row("SELECT COUNT(*) FROM responses", [], |row| row.get(0))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        // The connection doesn't hold any state between calls, so a poisoned lock is fine to reuse
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn try_load(&self, url: &Url) -> rusqlite::Result<Option<CacheEntry>> {
+        self.lock()
+            .query_row(
+                "SELECT body, fetched_at, status FROM responses WHERE url = ?1",
+                [canonical_url(url)],
+                |row| {
+                    Ok(CacheEntry {
+                        body: row.get(0)?,
+                        fetched_at: from_millis(row.get(1)?),
+                        status: row.get(2)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    fn try_save(&self, url: &Url, entry: &CacheEntry) -> rusqlite::Result<()> {
+        self.lock().execute(
+            "INSERT OR REPLACE INTO responses (url, endpoint, status, fetched_at, body)
+            VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                canonical_url(url),
+                url.path(),
+                entry.status,
+                to_millis(entry.fetched_at),
+                entry.body
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl CacheStore for SqliteCacheStore {
+    fn load(&self, url: &Url) -> Option<CacheEntry> {
+        match self.try_load(url) {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Can't load cached {url} <-- {}", error_chain(&e));
+                None
+            }
+        }
+    }
+
+    fn save(&self, url: &Url, entry: CacheEntry) {
+        if let Err(e) = self.try_save(url, &entry) {
+            eprintln!("Can't cache {url} <-- {}", error_chain(&e));
+        }
+    }
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlite_store() {
+        let store = SqliteCacheStore::open_in_memory().unwrap();
+        let url = |i| Url::parse(&format!("https://example.com/v1/odds?sportId={i}")).unwrap();
+
+        assert!(store.load(&url(1)).is_none());
+        store.save(&url(1), CacheEntry::new("1"));
+        store.save(&url(1), CacheEntry::new("one"));
+        let entry = store.load(&url(1)).unwrap();
+        assert_eq!(entry.body, "one");
+        assert_eq!(entry.status, 200);
+        assert!(entry.age() < Duration::from_secs(1));
+
+        let stale = CacheEntry {
+            fetched_at: SystemTime::now() - Duration::from_secs(120),
+            ..CacheEntry::new("2")
+        };
+        store.save(&url(2), stale);
+        assert_eq!(store.count().unwrap(), 2);
+        assert_eq!(store.purge(Duration::from_secs(60)).unwrap(), 1);
+        assert!(store.load(&url(2)).is_none());
+        store.vacuum().unwrap();
+        assert_eq!(store.count().unwrap(), 1);
+    }
+}
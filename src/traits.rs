@@ -1,11 +1,12 @@
 //! Traits
+use crate::client::{PinnacleClientError, RawResponse};
 use async_trait::async_trait;
 use reqwest::IntoUrl;
 use serde::{de::DeserializeOwned, Serialize};
 use std::error::Error;
 use std::marker::{Send, Sync};
 
-const API_ORIGIN: &str = "https://api.pinnacle.com";
+pub(crate) const API_ORIGIN: &str = "https://api.pinnacle.com";
 
 /// Describes Pinnacle API request
 pub trait PinnacleApiRequest {
@@ -16,6 +17,15 @@ pub trait PinnacleApiRequest {
     type Response: DeserializeOwned + Serialize + Send;
 }
 
+/// Describes Pinnacle API request sent as a json body of a POST request
+pub trait PinnacleApiPostRequest {
+    /// The API endpoint path
+    const PATH: &'static str;
+
+    /// The API response type
+    type Response: DeserializeOwned + Serialize + Send;
+}
+
 /// API Client
 #[async_trait]
 pub trait PinnacleApiClient {
@@ -28,13 +38,62 @@ pub trait PinnacleApiClient {
         U: IntoUrl + Send,
         T: DeserializeOwned + Serialize + Send;
 
+    /// GET request returning the response body undecoded.
+    ///
+    /// The default implementation re-encodes the body decoded by [`Self::get_by_url`], so
+    /// only successful responses are returned and the headers are lost.
+    async fn get_raw<U>(&self, url: U) -> Result<RawResponse, Self::Error>
+    where
+        U: IntoUrl + Send,
+    {
+        let body: serde_json::Value = self.get_by_url(url).await?;
+        Ok(RawResponse {
+            status: 200,
+            headers: Default::default(),
+            body: body.to_string(),
+        })
+    }
+
+    /// General POST request using full URL, the body is sent as json.
+    ///
+    /// The default implementation fails with [`PinnacleClientError::PostNotSupported`], so
+    /// clients which only read the API don't have to implement it.
+    async fn post_by_url<U, B, T>(&self, url: U, _body: &B) -> Result<T, Self::Error>
+    where
+        U: IntoUrl + Send,
+        B: Serialize + Sync,
+        T: DeserializeOwned + Serialize + Send,
+        Self::Error: From<PinnacleClientError>,
+    {
+        let url = url.into_url().map_err(PinnacleClientError::from)?;
+        Err(PinnacleClientError::PostNotSupported(url).into())
+    }
+
     /// Typed GET request
     async fn get<Q>(&self, query: &Q) -> Result<Q::Response, Self::Error>
     where
         Q: PinnacleApiRequest + Send + Serialize + Sync,
     {
-        let qs = serde_urlencoded::to_string(query).ok().unwrap_or_default();
-        let url = format!("{API_ORIGIN}{}?{qs}", Q::PATH);
-        self.get_by_url(url).await
+        self.get_by_url(request_url(query)).await
+    }
+
+    /// Typed POST request
+    async fn post<Q>(&self, body: &Q) -> Result<Q::Response, Self::Error>
+    where
+        Q: PinnacleApiPostRequest + Send + Serialize + Sync,
+        Self::Error: From<PinnacleClientError>,
+    {
+        self.post_by_url(post_request_url::<Q>(), body).await
     }
 }
+
+/// Full URL of the request
+pub(crate) fn request_url<Q: PinnacleApiRequest + Serialize>(query: &Q) -> String {
+    let qs = serde_urlencoded::to_string(query).ok().unwrap_or_default();
+    format!("{API_ORIGIN}{}?{qs}", Q::PATH)
+}
+
+/// Full URL of the POST request
+pub(crate) fn post_request_url<Q: PinnacleApiPostRequest>() -> String {
+    format!("{API_ORIGIN}{}", Q::PATH)
+}
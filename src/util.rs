@@ -1,5 +1,15 @@
 //! Utilities
+use crate::client::PinnacleClientError;
+use chrono::{DateTime, Utc};
 use serde::de::DeserializeOwned;
+#[cfg(feature = "extra-fields")]
+use std::{cell::RefCell, collections::BTreeSet};
+
+#[cfg(feature = "extra-fields")]
+thread_local! {
+    static EXTRA_FIELDS: RefCell<Option<BTreeSet<String>>> = const { RefCell::new(None) };
+    static SKIP_EXTRA_FIELDS: std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+}
 
 type DeserializeJsonError = serde_path_to_error::Error<serde_json::Error>;
 
@@ -9,6 +19,17 @@ pub fn parse_json<T: DeserializeOwned>(json: &str) -> Result<T, DeserializeJsonE
     serde_path_to_error::deserialize(jd)
 }
 
+/// Parses the start time of a fixture, warning if it isn't RFC 3339
+pub fn parse_starts(starts: &str) -> Option<DateTime<Utc>> {
+    match starts.parse() {
+        Ok(starts) => Some(starts),
+        Err(e) => {
+            eprintln!("Can't parse start time {starts:?} <-- {e}");
+            None
+        }
+    }
+}
+
 /// A helper function to format an error with its source chain.
 ///
 /// This function works with both `&Error` and `Box<dyn Error>`. When passing a boxed error,
@@ -28,6 +49,74 @@ pub fn error_chain(e: &(dyn std::error::Error + 'static)) -> String {
     s
 }
 
+/// Whether the error, or one of its sources, means the request definitely had no effect
+pub(crate) fn is_definitive_failure(e: &(dyn std::error::Error + 'static)) -> bool {
+    let mut current = Some(e);
+    while let Some(e) = current {
+        if let Some(e) = e.downcast_ref::<PinnacleClientError>() {
+            return e.is_definitive_failure();
+        }
+        current = e.source();
+    }
+    false
+}
+
+/// Deserializes the fields a response struct doesn't model, noting their names for
+/// [`collect_extra_fields`]
+#[cfg(feature = "extra-fields")]
+pub(crate) fn deserialize_extra<'de, D>(
+    deserializer: D,
+) -> Result<serde_json::Map<String, serde_json::Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    use serde::Deserialize;
+
+    let extra = serde_json::Map::deserialize(deserializer)?;
+    if !extra.is_empty() {
+        EXTRA_FIELDS.with(|names| {
+            if let Some(names) = names.borrow_mut().as_mut() {
+                names.extend(extra.keys().cloned());
+            }
+        });
+    }
+    Ok(extra)
+}
+
+/// Serializes the unmodeled fields, unless within [`without_extra_fields`]
+#[cfg(feature = "extra-fields")]
+pub(crate) fn serialize_extra<S>(
+    extra: &serde_json::Map<String, serde_json::Value>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    if SKIP_EXTRA_FIELDS.with(|skip| skip.get()) {
+        serializer.collect_map(std::iter::empty::<(String, serde_json::Value)>())
+    } else {
+        serializer.collect_map(extra)
+    }
+}
+
+/// Runs the encoding leaving out the unmodeled fields
+#[cfg(feature = "extra-fields")]
+pub(crate) fn without_extra_fields<T>(encode: impl FnOnce() -> T) -> T {
+    let outer = SKIP_EXTRA_FIELDS.with(|skip| skip.replace(true));
+    let encoded = encode();
+    SKIP_EXTRA_FIELDS.with(|skip| skip.set(outer));
+    encoded
+}
+
+/// Runs the decoding, returning names of the unmodeled fields met on the way
+#[cfg(feature = "extra-fields")]
+pub(crate) fn collect_extra_fields<T>(decode: impl FnOnce() -> T) -> (T, BTreeSet<String>) {
+    let outer = EXTRA_FIELDS.with(|names| names.replace(Some(BTreeSet::new())));
+    let decoded = decode();
+    let names = EXTRA_FIELDS.with(|names| names.replace(outer));
+    (decoded, names.unwrap_or_default())
+}
+
 pub(crate) fn serialize_comma_separated_option<T, S>(
     data: &Option<Vec<T>>,
     serializer: S,
@@ -36,7 +125,9 @@ where
     S: serde::Serializer,
     T: ToString,
 {
-    let Some(items) = data else { return serializer.serialize_none() };
+    let Some(items) = data else {
+        return serializer.serialize_none();
+    };
     let comma_separated = items
         .iter()
         .map(|num| num.to_string())
@@ -56,6 +147,20 @@ where
     }
 }
 
+/// Serializes a unit enum variant in upper case, e.g. `Decimal` as `DECIMAL`
+pub(crate) fn serialize_uppercase<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+    T: serde::Serialize,
+{
+    use serde::ser::Error;
+
+    match serde_json::to_value(value).map_err(S::Error::custom)? {
+        serde_json::Value::String(s) => serializer.serialize_str(&s.to_uppercase()),
+        _ => Err(S::Error::custom("expected a unit enum variant")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,4 +200,22 @@ mod tests {
         assert_eq!(qs(&S { is: false }), "");
         assert_eq!(qs(&S { is: true }), "is=1");
     }
+
+    #[cfg(feature = "extra-fields")]
+    #[test]
+    fn test_extra_fields() {
+        use crate::responses::SportsResponse;
+
+        let json = r#"{"total": 1, "sports": [{"id": 29, "name": "Soccer", "hasOfferings": true,
+            "leagueSpecialsCount": 0, "eventSpecialsCount": 0, "eventCount": 1, "isNew": true}]}"#;
+        let (sports, names) = collect_extra_fields(|| parse_json::<SportsResponse>(json));
+        let sports = sports.unwrap();
+        assert_eq!(sports.sports[0].extra["isNew"], true);
+        assert_eq!(names, BTreeSet::from(["isNew".into(), "total".into()]));
+
+        let encoded = serde_json::to_value(&sports).unwrap();
+        assert_eq!(encoded["total"], 1);
+        let encoded = without_extra_fields(|| serde_json::to_value(&sports).unwrap());
+        assert!(encoded.get("total").is_none());
+    }
 }